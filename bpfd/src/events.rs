@@ -0,0 +1,101 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Authors of bpfd
+
+//! Program-lifecycle event stream.
+//!
+//! Operators otherwise learn about load/attach/unload only by scraping the
+//! daemon's `info!` logs. This subsystem gives every state transition a
+//! structured [`ProgramEvent`] published on a broadcast channel. The gRPC
+//! layer exposes a server-streaming RPC that subscribes to the channel
+//! (optionally filtered by program id or type) so external controllers and
+//! dashboards can react in real time. The `BpfManager` calls [`emit`] on each
+//! transition.
+
+use tokio::sync::broadcast;
+
+/// The capacity of the lifecycle broadcast channel. Slow subscribers that fall
+/// behind are lagged rather than blocking the manager.
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// A single program-lifecycle transition.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProgramEvent {
+    /// A program's bytecode is being loaded.
+    Loading,
+    /// A program finished loading and was assigned a kernel id.
+    Loaded { id: u32 },
+    /// A program was attached to its hook.
+    Attached,
+    /// A program was unloaded.
+    Unloaded,
+    /// A transition failed.
+    Error { msg: String },
+}
+
+/// A lifecycle event tagged with the program it concerns, so subscribers can
+/// filter without re-deriving the source.
+#[derive(Clone, Debug)]
+pub struct TaggedEvent {
+    /// The bpfman program id, when known (a `Loading` event precedes id
+    /// assignment).
+    pub program_id: Option<u32>,
+    /// The kernel program type, when known.
+    pub program_type: Option<u32>,
+    pub event: ProgramEvent,
+}
+
+/// A subscriber's filter. `None` fields match everything.
+#[derive(Clone, Debug, Default)]
+pub struct EventFilter {
+    pub program_id: Option<u32>,
+    pub program_type: Option<u32>,
+}
+
+impl EventFilter {
+    /// Whether `event` should be delivered to a subscriber with this filter.
+    pub fn matches(&self, event: &TaggedEvent) -> bool {
+        if let Some(id) = self.program_id {
+            if event.program_id != Some(id) {
+                return false;
+            }
+        }
+        if let Some(ty) = self.program_type {
+            if event.program_type != Some(ty) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// The publish side of the lifecycle stream, cloned into the `BpfManager`.
+#[derive(Clone, Debug)]
+pub struct EventSender {
+    tx: broadcast::Sender<TaggedEvent>,
+}
+
+impl EventSender {
+    /// Create a new broadcast channel and its sender.
+    pub fn new() -> Self {
+        let (tx, _rx) = broadcast::channel(CHANNEL_CAPACITY);
+        Self { tx }
+    }
+
+    /// Publish an event. Sending never blocks; it is a no-op when no client is
+    /// currently subscribed.
+    pub fn emit(&self, event: TaggedEvent) {
+        // A send error only means there are no live receivers; that is fine.
+        let _ = self.tx.send(event);
+    }
+
+    /// Subscribe to the stream, receiving every event published from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<TaggedEvent> {
+        self.tx.subscribe()
+    }
+}
+
+impl Default for EventSender {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,266 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Authors of bpfd
+
+//! Filesystem abstraction for the bpffs and on-disk artifacts.
+//!
+//! The load/unload logic touches `std::fs`/`nix` directly, which makes it
+//! impossible to unit-test pin-path layout and permission application without
+//! root and a real bpffs mount. The [`Filesystem`] trait captures the handful
+//! of operations those paths need so the syscall-backed implementation can be
+//! swapped for an in-memory one in tests.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use crate::{
+    errors::BpfdError,
+    utils::{self, should_map_be_pinned},
+};
+
+/// The filesystem operations performed by the bpffs and artifact code paths.
+///
+/// The default implementation is [`SyscallFs`], which delegates to the same
+/// `std::fs`/`nix`/[`crate::utils`] calls used previously. [`InMemoryFs`] is a
+/// deterministic backend for tests.
+#[async_trait::async_trait]
+pub(crate) trait Filesystem: Send + Sync {
+    /// Read the whole contents of `path`.
+    async fn read(&self, path: &Path) -> Result<Vec<u8>, BpfdError>;
+
+    /// Read the whole contents of `path` as a UTF-8 string.
+    async fn read_to_string(&self, path: &Path) -> Result<String, BpfdError>;
+
+    /// Write `bytes` to `path`, replacing any existing content.
+    async fn write(&self, path: &Path, bytes: &[u8]) -> Result<(), BpfdError>;
+
+    /// Create the directory `path`, including parents.
+    fn mkdir(&self, path: &Path) -> Result<(), BpfdError>;
+
+    /// List the immediate children of `path`.
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>, BpfdError>;
+
+    /// Mount a bpffs at `path`.
+    fn create_bpffs(&self, path: &Path) -> Result<(), BpfdError>;
+
+    /// Apply `mode` to `path`.
+    fn set_permissions(&self, path: &Path, mode: u32) -> Result<(), BpfdError>;
+
+    /// Whether a map with the given name should be pinned (skips the internal
+    /// `.rodata`/`.bss`/`.data` maps).
+    fn should_map_be_pinned(&self, name: &str) -> bool {
+        should_map_be_pinned(name)
+    }
+
+    /// Apply `mode` to every pinnable map directly under `dir`, leaving the
+    /// internal `.rodata`/`.bss`/`.data` maps untouched. Expressing this
+    /// permission pass against the trait lets it be exercised with
+    /// [`InMemoryFs`] instead of a real bpffs mount. The load path does not
+    /// call it; its only callers are this module's tests.
+    fn apply_map_permissions(&self, dir: &Path, mode: u32) -> Result<(), BpfdError> {
+        for entry in self.read_dir(dir)? {
+            let pin = entry
+                .file_name()
+                .map(|n| self.should_map_be_pinned(&n.to_string_lossy()))
+                .unwrap_or(true);
+            if pin {
+                self.set_permissions(&entry, mode)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The production [`Filesystem`], backed by real syscalls.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct SyscallFs;
+
+#[async_trait::async_trait]
+impl Filesystem for SyscallFs {
+    async fn read(&self, path: &Path) -> Result<Vec<u8>, BpfdError> {
+        utils::read(path).await
+    }
+
+    async fn read_to_string(&self, path: &Path) -> Result<String, BpfdError> {
+        utils::read_to_string(path).await
+    }
+
+    async fn write(&self, path: &Path, bytes: &[u8]) -> Result<(), BpfdError> {
+        tokio::fs::write(path, bytes)
+            .await
+            .map_err(|e| BpfdError::Error(format!("can't write file: {e}")))
+    }
+
+    fn mkdir(&self, path: &Path) -> Result<(), BpfdError> {
+        std::fs::create_dir_all(path)
+            .map_err(|e| BpfdError::Error(format!("can't create dir: {e}")))
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>, BpfdError> {
+        let mut entries = Vec::new();
+        for entry in std::fs::read_dir(path)
+            .map_err(|e| BpfdError::Error(format!("can't read dir: {e}")))?
+        {
+            let entry = entry.map_err(|e| BpfdError::Error(format!("can't read entry: {e}")))?;
+            entries.push(entry.path());
+        }
+        Ok(entries)
+    }
+
+    fn create_bpffs(&self, path: &Path) -> Result<(), BpfdError> {
+        utils::create_bpffs(&path.to_string_lossy())
+            .map_err(|e| BpfdError::Error(e.to_string()))
+    }
+
+    fn set_permissions(&self, path: &Path, mode: u32) -> Result<(), BpfdError> {
+        utils::set_file_permissions(&path.to_string_lossy(), mode);
+        Ok(())
+    }
+}
+
+/// An in-memory [`Filesystem`] for tests. Files and their modes live in a map;
+/// directories are implicit in the stored paths. No real bpffs mount or root
+/// privileges are required.
+#[derive(Debug, Default)]
+pub(crate) struct InMemoryFs {
+    inner: Mutex<BTreeMap<PathBuf, InMemoryNode>>,
+}
+
+#[derive(Debug, Clone)]
+enum InMemoryNode {
+    File { bytes: Vec<u8>, mode: u32 },
+    Dir,
+}
+
+#[async_trait::async_trait]
+impl Filesystem for InMemoryFs {
+    async fn read(&self, path: &Path) -> Result<Vec<u8>, BpfdError> {
+        match self.inner.lock().unwrap().get(path) {
+            Some(InMemoryNode::File { bytes, .. }) => Ok(bytes.clone()),
+            _ => Err(BpfdError::Error(format!("{} not found", path.display()))),
+        }
+    }
+
+    async fn read_to_string(&self, path: &Path) -> Result<String, BpfdError> {
+        let bytes = self.read(path).await?;
+        String::from_utf8(bytes)
+            .map_err(|e| BpfdError::Error(format!("can't read file as utf-8: {e}")))
+    }
+
+    async fn write(&self, path: &Path, bytes: &[u8]) -> Result<(), BpfdError> {
+        self.inner.lock().unwrap().insert(
+            path.to_path_buf(),
+            InMemoryNode::File {
+                bytes: bytes.to_vec(),
+                mode: 0o0644,
+            },
+        );
+        Ok(())
+    }
+
+    fn mkdir(&self, path: &Path) -> Result<(), BpfdError> {
+        let mut inner = self.inner.lock().unwrap();
+        for ancestor in path.ancestors() {
+            inner
+                .entry(ancestor.to_path_buf())
+                .or_insert(InMemoryNode::Dir);
+        }
+        Ok(())
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>, BpfdError> {
+        let inner = self.inner.lock().unwrap();
+        Ok(inner
+            .keys()
+            .filter(|p| p.parent() == Some(path))
+            .cloned()
+            .collect())
+    }
+
+    fn create_bpffs(&self, path: &Path) -> Result<(), BpfdError> {
+        self.mkdir(path)
+    }
+
+    fn set_permissions(&self, path: &Path, mode: u32) -> Result<(), BpfdError> {
+        match self.inner.lock().unwrap().get_mut(path) {
+            Some(InMemoryNode::File { mode: m, .. }) => {
+                *m = mode;
+                Ok(())
+            }
+            Some(InMemoryNode::Dir) => Ok(()),
+            None => Err(BpfdError::Error(format!("{} not found", path.display()))),
+        }
+    }
+}
+
+#[cfg(test)]
+impl InMemoryFs {
+    /// The mode of a stored file, or `None` if it is absent or a directory.
+    fn mode_of(&self, path: &Path) -> Option<u32> {
+        match self.inner.lock().unwrap().get(path) {
+            Some(InMemoryNode::File { mode, .. }) => Some(*mode),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn write_then_read_roundtrips() {
+        let fs = InMemoryFs::default();
+        let path = Path::new("/maps/trace");
+        fs.write(path, b"hello").await.unwrap();
+        assert_eq!(fs.read(path).await.unwrap(), b"hello");
+        assert_eq!(fs.read_to_string(path).await.unwrap(), "hello");
+    }
+
+    #[tokio::test]
+    async fn read_missing_path_errors() {
+        let fs = InMemoryFs::default();
+        assert!(fs.read(Path::new("/nope")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn read_to_string_rejects_non_utf8() {
+        let fs = InMemoryFs::default();
+        let path = Path::new("/maps/binary");
+        fs.write(path, &[0xff, 0xfe]).await.unwrap();
+        assert!(fs.read_to_string(path).await.is_err());
+    }
+
+    #[test]
+    fn mkdir_creates_ancestors_and_read_dir_lists_children() {
+        let fs = InMemoryFs::default();
+        fs.mkdir(Path::new("/a/b/c")).unwrap();
+        let children = fs.read_dir(Path::new("/a")).unwrap();
+        assert_eq!(children, vec![PathBuf::from("/a/b")]);
+    }
+
+    #[tokio::test]
+    async fn apply_map_permissions_skips_internal_maps() {
+        let fs = InMemoryFs::default();
+        fs.mkdir(Path::new("/prog")).unwrap();
+        fs.write(Path::new("/prog/trace_map"), b"").await.unwrap();
+        fs.write(Path::new("/prog/.rodata"), b"").await.unwrap();
+
+        fs.apply_map_permissions(Path::new("/prog"), 0o600).unwrap();
+
+        // The real map is re-moded; the internal map keeps its default.
+        assert_eq!(fs.mode_of(Path::new("/prog/trace_map")), Some(0o600));
+        assert_eq!(fs.mode_of(Path::new("/prog/.rodata")), Some(0o644));
+    }
+
+    #[test]
+    fn syscall_fs_defers_map_pinning_to_shared_helper() {
+        // The production backend shares the same pinning predicate, so the two
+        // implementations cannot disagree about which maps to touch.
+        let fs = SyscallFs;
+        assert!(fs.should_map_be_pinned("trace_map"));
+        assert!(!fs.should_map_be_pinned(".rodata"));
+    }
+}
@@ -0,0 +1,73 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Authors of bpfd
+
+//! Singleton guard for the daemon.
+//!
+//! Two `bpfd` processes managing the same kernel/BPF state corrupt each
+//! other's maps and sockets. Before binding any endpoint, `serve()` acquires
+//! an advisory `flock` on `bpfd.lock` in the state directory and records its
+//! PID. If the lock is already held by a live process the new daemon refuses
+//! to start; only once the lock is held do we know any leftover socket belongs
+//! to a dead instance and can be safely removed.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    os::{fd::AsRawFd, unix::fs::OpenOptionsExt},
+    path::{Path, PathBuf},
+    process,
+};
+
+use anyhow::{bail, Context, Result};
+use nix::fcntl::{flock, FlockArg};
+
+/// The lockfile name inside the state directory.
+const LOCK_FILE: &str = "bpfd.lock";
+
+/// An acquired singleton lock. The `flock` is released when the file is
+/// dropped, i.e. when the daemon exits.
+#[derive(Debug)]
+pub struct DaemonLock {
+    _file: File,
+    path: PathBuf,
+}
+
+impl DaemonLock {
+    /// Acquire the singleton lock in `state_dir`, failing if another live
+    /// daemon already holds it.
+    pub fn acquire<P: AsRef<Path>>(state_dir: P) -> Result<Self> {
+        let path = state_dir.as_ref().join(LOCK_FILE);
+        std::fs::create_dir_all(state_dir.as_ref())?;
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .mode(0o600)
+            .open(&path)
+            .with_context(|| format!("unable to open lockfile {}", path.display()))?;
+
+        if flock(file.as_raw_fd(), FlockArg::LockExclusiveNonblock).is_err() {
+            let mut existing = String::new();
+            file.read_to_string(&mut existing).ok();
+            let owner = existing.trim();
+            bail!(
+                "another bpfd instance (pid {}) is already running; refusing to start",
+                if owner.is_empty() { "unknown" } else { owner }
+            );
+        }
+
+        // We hold the lock: record our PID, replacing any stale owner.
+        file.seek(SeekFrom::Start(0))?;
+        file.set_len(0)?;
+        write!(file, "{}", process::id())?;
+        file.flush()?;
+
+        Ok(Self { _file: file, path })
+    }
+
+    /// The path of the held lockfile.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
@@ -0,0 +1,285 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Authors of bpfd
+
+//! Peer-to-peer distribution of bytecode images between paired bpfd nodes.
+//!
+//! [`ImageManager`](crate::oci_utils::ImageManager) pulls each content-addressed
+//! bytecode image into [`STDIR_BYTECODE_IMAGE_CONTENT_STORE`] from an upstream
+//! registry, once per node. In air-gapped or bandwidth-limited clusters that is
+//! wasteful: a fleet of nodes re-downloads the same signed bytes over the slow
+//! link. This subsystem lets paired nodes gossip which digests they already
+//! hold and copy a missing image directly from a peer instead.
+//!
+//! Each node runs a small [`PeerService`] gRPC endpoint — reusing the same
+//! mutual TLS as the remote control endpoint, so only paired nodes talk to each
+//! other — that advertises the digests in its content store and serves their
+//! bytes. When the image manager needs a digest it is
+//! missing, [`PeerManager::resolve`] asks each configured peer whether it
+//! advertises that digest, fetches the blob from the first that does, verifies
+//! the bytes against the requested digest before admitting them to the store,
+//! and returns `None` when no peer has it so the caller falls back to the
+//! registry.
+//!
+//! [`STDIR_BYTECODE_IMAGE_CONTENT_STORE`]: bpfd_api::util::directories::STDIR_BYTECODE_IMAGE_CONTENT_STORE
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use bpfd_api::v1::{
+    peer_client::PeerClient as PeerRpcClient,
+    peer_server::{Peer, PeerServer},
+    AdvertiseRequest, AdvertiseResponse, FetchBlobRequest, FetchBlobResponse,
+};
+use log::{debug, info, warn};
+use sha2::{Digest as _, Sha256};
+use tonic::{transport::Channel, Request, Response, Status};
+
+/// The `sha256:` algorithm prefix every content-addressed digest carries.
+const DIGEST_PREFIX: &str = "sha256:";
+
+/// Mode for a blob admitted to the content store: readable by owner and group,
+/// matching the bytecode images already pulled from a registry.
+const BLOB_MODE: u32 = 0o0640;
+
+/// A view over the local bytecode content store used by both the serving and
+/// resolving halves of the peer subsystem.
+#[derive(Clone, Debug)]
+pub struct ContentStore {
+    root: PathBuf,
+}
+
+impl ContentStore {
+    /// Open the content store rooted at `root`.
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    /// The on-disk path a blob with `digest` is stored at. Blobs are laid out
+    /// by their hex digest so lookup is a single `stat`.
+    fn blob_path(&self, digest: &str) -> PathBuf {
+        self.root.join("blobs").join(digest.replace(':', "/"))
+    }
+
+    /// Whether this node already holds `digest`.
+    pub fn holds(&self, digest: &str) -> bool {
+        self.blob_path(digest).is_file()
+    }
+
+    /// The digests of every blob currently in the store, for advertisement.
+    pub fn held_digests(&self) -> Vec<String> {
+        let blobs = self.root.join("blobs").join("sha256");
+        let Ok(entries) = fs::read_dir(&blobs) else {
+            return Vec::new();
+        };
+        entries
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().is_file())
+            .filter_map(|e| e.file_name().into_string().ok())
+            .map(|name| format!("{DIGEST_PREFIX}{name}"))
+            .collect()
+    }
+
+    /// Read the bytes of a held blob.
+    pub fn read(&self, digest: &str) -> Result<Vec<u8>> {
+        fs::read(self.blob_path(digest))
+            .with_context(|| format!("content store does not hold {digest}"))
+    }
+
+    /// Admit `bytes` to the store under `digest`, but only after verifying the
+    /// content hashes to the claimed digest — peers are authenticated, yet we
+    /// never trust fetched bytes without re-checking them against their name.
+    ///
+    /// The blob is written atomically with [`write_atomic`](crate::utils::write_atomic)
+    /// so its permissions are applied before the content is visible and a
+    /// concurrent reader never observes a half-written image.
+    pub async fn admit(&self, digest: &str, bytes: &[u8]) -> Result<()> {
+        verify_digest(digest, bytes)?;
+        let path = self.blob_path(digest);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let path = path
+            .to_str()
+            .with_context(|| format!("blob path {} is not valid UTF-8", path.display()))?;
+        crate::utils::write_atomic(path, bytes, BLOB_MODE)
+            .await
+            .map_err(|e| anyhow::anyhow!("unable to write blob {digest} to content store: {e}"))?;
+        Ok(())
+    }
+}
+
+/// Verify that `bytes` hashes to `digest`, which must be a `sha256:` digest.
+fn verify_digest(digest: &str, bytes: &[u8]) -> Result<()> {
+    let expected = digest
+        .strip_prefix(DIGEST_PREFIX)
+        .with_context(|| format!("unsupported digest algorithm in {digest}"))?;
+    let mut hasher = Sha256::new();
+    hasher.update(bytes);
+    let actual = hex::encode(hasher.finalize());
+    if actual != expected {
+        bail!("digest mismatch: expected {expected}, got {actual}");
+    }
+    Ok(())
+}
+
+/// The gRPC service a node exposes to its peers, backed by the local content
+/// store.
+#[derive(Debug)]
+pub struct PeerService {
+    store: ContentStore,
+}
+
+impl PeerService {
+    /// Wrap `store` as a servable peer endpoint.
+    pub fn new(store: ContentStore) -> Self {
+        Self { store }
+    }
+
+    /// Consume the service into a tonic [`PeerServer`] ready to add to a
+    /// `Server` builder.
+    pub fn into_server(self) -> PeerServer<Self> {
+        PeerServer::new(self)
+    }
+}
+
+#[tonic::async_trait]
+impl Peer for PeerService {
+    async fn advertise(
+        &self,
+        _request: Request<AdvertiseRequest>,
+    ) -> Result<Response<AdvertiseResponse>, Status> {
+        Ok(Response::new(AdvertiseResponse {
+            digests: self.store.held_digests(),
+        }))
+    }
+
+    async fn fetch_blob(
+        &self,
+        request: Request<FetchBlobRequest>,
+    ) -> Result<Response<FetchBlobResponse>, Status> {
+        let digest = request.into_inner().digest;
+        match self.store.read(&digest) {
+            Ok(bytes) => Ok(Response::new(FetchBlobResponse { bytes })),
+            Err(_) => Err(Status::not_found(format!("no blob for {digest}"))),
+        }
+    }
+}
+
+/// A single paired peer the resolver can query.
+#[async_trait]
+pub trait PeerEndpoint: Send + Sync {
+    /// The digests the peer currently advertises.
+    async fn advertise(&self) -> Result<Vec<String>>;
+
+    /// Fetch the bytes of `digest` from the peer.
+    async fn fetch_blob(&self, digest: &str) -> Result<Vec<u8>>;
+}
+
+/// A [`PeerEndpoint`] backed by a tonic channel to a remote bpfd over mutual
+/// TLS.
+pub struct TonicPeerEndpoint {
+    channel: Channel,
+    addr: String,
+}
+
+impl TonicPeerEndpoint {
+    /// Wrap an already-connected `channel` to the peer at `addr`.
+    pub fn new(channel: Channel, addr: String) -> Self {
+        Self { channel, addr }
+    }
+}
+
+#[async_trait]
+impl PeerEndpoint for TonicPeerEndpoint {
+    async fn advertise(&self) -> Result<Vec<String>> {
+        let mut client = PeerRpcClient::new(self.channel.clone());
+        let response = client
+            .advertise(AdvertiseRequest {})
+            .await
+            .with_context(|| format!("advertise request to peer {} failed", self.addr))?;
+        Ok(response.into_inner().digests)
+    }
+
+    async fn fetch_blob(&self, digest: &str) -> Result<Vec<u8>> {
+        let mut client = PeerRpcClient::new(self.channel.clone());
+        let response = client
+            .fetch_blob(FetchBlobRequest {
+                digest: digest.to_string(),
+            })
+            .await
+            .with_context(|| format!("fetch of {digest} from peer {} failed", self.addr))?;
+        Ok(response.into_inner().bytes)
+    }
+}
+
+/// Resolves missing bytecode images from paired peers before the registry is
+/// consulted.
+pub struct PeerManager {
+    store: ContentStore,
+    peers: Vec<Box<dyn PeerEndpoint>>,
+}
+
+impl PeerManager {
+    /// Build a resolver over `store` that will query `peers` in order.
+    pub fn new(store: ContentStore, peers: Vec<Box<dyn PeerEndpoint>>) -> Self {
+        Self { store, peers }
+    }
+
+    /// Whether any peers are configured. With none, [`resolve`](Self::resolve)
+    /// is always a no-op and the caller goes straight to the registry.
+    pub fn has_peers(&self) -> bool {
+        !self.peers.is_empty()
+    }
+
+    /// Try to satisfy `digest` from a peer. Returns the admitted bytes on
+    /// success, or `None` when no peer advertises it, in which case the caller
+    /// should fall back to the upstream registry. Bytes are verified against
+    /// `digest` before being admitted to the content store.
+    pub async fn resolve(&self, digest: &str) -> Option<Vec<u8>> {
+        if self.store.holds(digest) {
+            return self.store.read(digest).ok();
+        }
+        for peer in &self.peers {
+            match peer.advertise().await {
+                Ok(digests) if digests.iter().any(|d| d == digest) => {}
+                Ok(_) => continue,
+                Err(e) => {
+                    debug!("Peer advertisement failed: {e}");
+                    continue;
+                }
+            }
+            let bytes = match peer.fetch_blob(digest).await {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    warn!("Peer advertised {digest} but serving it failed: {e}");
+                    continue;
+                }
+            };
+            match self.store.admit(digest, &bytes).await {
+                Ok(()) => {
+                    info!("Fetched bytecode image {digest} from peer");
+                    return Some(bytes);
+                }
+                Err(e) => {
+                    // A verification failure means the peer served bytes that
+                    // do not match the digest; skip it rather than poison the
+                    // store, and let another peer or the registry answer.
+                    warn!("Discarding blob {digest} fetched from peer: {e}");
+                    continue;
+                }
+            }
+        }
+        None
+    }
+
+    /// The content store this manager admits fetched blobs into.
+    pub fn store(&self) -> &ContentStore {
+        &self.store
+    }
+}
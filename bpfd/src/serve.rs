@@ -1,15 +1,16 @@
 // SPDX-License-Identifier: Apache-2.0
 // Copyright Authors of bpfd
 
-use std::{fs::remove_file, path::Path};
+use std::{fs::remove_file, net::SocketAddr, path::Path, sync::Arc};
 
 use bpfd_api::{
     config::{self, Config},
-    util::directories::STDIR_BYTECODE_IMAGE_CONTENT_STORE,
+    util::directories::{STDIR, STDIR_BYTECODE_IMAGE_CONTENT_STORE},
     v1::bpfd_server::BpfdServer,
 };
 
 use log::{debug, info};
+use nix::sys::stat::{umask, Mode};
 use tokio::{
     net::UnixListener,
     runtime::Runtime,
@@ -19,14 +20,19 @@ use tokio::{
     task::JoinHandle,
 };
 use tokio_stream::wrappers::UnixListenerStream;
-use tonic::transport::Server;
+use tonic::transport::{Channel, Server};
 
 use crate::{
     bpf::BpfManager,
+    events::EventSender,
+    lock::DaemonLock,
     oci_utils::ImageManager,
+    peer::{ContentStore, PeerEndpoint, PeerManager, PeerService, TonicPeerEndpoint},
     rpc::BpfdLoader,
     static_program::get_static_programs,
     storage::StorageManager,
+    supervisor::{ChannelReconciler, Supervisor, SupervisorControl, SupervisorRequest},
+    tls::{client_tls_config, server_tls_config},
     utils::{set_file_permissions, SOCK_MODE},
 };
 
@@ -36,9 +42,19 @@ pub fn serve(
     static_program_path: &str,
     csi_support: bool,
 ) -> anyhow::Result<()> {
+    // Guard against a second daemon clobbering our sockets and kernel state.
+    // Holding this lock is also what makes removing a leftover socket in
+    // `serve_unix` safe: if we hold it, any previous owner is gone.
+    let _daemon_lock = DaemonLock::acquire(STDIR)?;
+
     let (tx, rx) = mpsc::channel(32);
 
-    let loader = BpfdLoader::new(tx.clone());
+    // Lifecycle events are published by the BpfManager and streamed to
+    // subscribers by the gRPC layer. The loader holds the subscribe side; the
+    // manager holds the publish side.
+    let events = EventSender::new();
+
+    let loader = BpfdLoader::new(tx.clone(), events.clone());
     let service = BpfdServer::new(loader);
 
     let endpoints = config.grpc.endpoints.clone();
@@ -57,6 +73,17 @@ pub fn serve(
                         Err(e) => eprintln!("Error = {e:?}"),
                     }
                 }
+                config::Endpoint::Tcp { addr, enabled, tls } => {
+                    if !enabled {
+                        info!("Skipping disabled endpoint on {addr}");
+                        continue;
+                    }
+
+                    match serve_tcp(addr, tls, service.clone()).await {
+                        Ok(handle) => listeners.push(handle),
+                        Err(e) => eprintln!("Error = {e:?}"),
+                    }
+                }
             }
         }
         for listener in listeners {
@@ -67,48 +94,208 @@ pub fn serve(
         }
     });
 
+    // Assemble the peer-to-peer image resolver. When peering is enabled the
+    // image manager consults paired nodes for a missing digest before reaching
+    // for the upstream registry; peer connections reuse the node's mTLS
+    // identity. With peering disabled the resolver holds no peers and every
+    // pull goes straight to the registry.
+    let content_store = ContentStore::new(STDIR_BYTECODE_IMAGE_CONTENT_STORE);
+    let mut peer_endpoints: Vec<Box<dyn PeerEndpoint>> = Vec::new();
+    if let Some(peering) = config.peering.as_ref().filter(|p| p.enabled) {
+        let client_cert = std::fs::read(&peering.tls.cert)?;
+        let client_key = std::fs::read(&peering.tls.key)?;
+        let ca_cert = std::fs::read(&peering.tls.ca_cert)?;
+        let tls = client_tls_config(&client_cert, &client_key, &ca_cert);
+        for addr in &peering.peers {
+            let channel = Channel::from_shared(addr.clone())?
+                .tls_config(tls.clone())?
+                .connect_lazy();
+            peer_endpoints.push(Box::new(TonicPeerEndpoint::new(channel, addr.clone())));
+        }
+    }
+    let peer_manager = Arc::new(PeerManager::new(content_store, peer_endpoints));
+
+    // Serve our own content store to peers so the distribution is
+    // bidirectional: a node both fetches missing images from peers and serves
+    // the images it already holds back to them.
+    let mut peer_server_handle = None;
+    if let Some(peering) = config.peering.as_ref().filter(|p| p.enabled) {
+        let peer_service = PeerService::new(peer_manager.store().clone());
+        match runtime.block_on(serve_peer(peering.listen, peering.tls.clone(), peer_service)) {
+            Ok(handle) => peer_server_handle = Some(handle),
+            Err(e) => eprintln!("Error = {e:?}"),
+        }
+    }
+
     let allow_unsigned = config.signing.as_ref().map_or(true, |s| s.allow_unsigned);
     let (itx, irx) = mpsc::channel(32);
 
-    let mut image_manager =
-        ImageManager::new(STDIR_BYTECODE_IMAGE_CONTENT_STORE, allow_unsigned, irx)?;
+    let mut image_manager = ImageManager::new(
+        STDIR_BYTECODE_IMAGE_CONTENT_STORE,
+        allow_unsigned,
+        irx,
+        peer_manager,
+    )?;
     let image_manager_handle = runtime.spawn(async move {
         image_manager.run().await;
     });
 
-    let mut bpf_manager = BpfManager::new(config, rx, itx);
+    let mut bpf_manager = BpfManager::new(config, rx, itx, events);
     bpf_manager.rebuild_state()?;
 
-    let static_programs = get_static_programs(static_program_path)?;
+    // Retained so a SIGHUP can re-read the static-program directory.
+    let static_program_path = static_program_path.to_string();
+    let static_programs = get_static_programs(&static_program_path)?;
 
-    // Load any static programs first
+    // Load any static programs first, remembering each spec and its kernel id
+    // so the supervisor can reload it if it later disappears.
+    let mut supervised: Vec<(_, u32)> = Vec::new();
     if !static_programs.is_empty() {
         for prog in static_programs {
+            let spec = prog.clone();
             let ret_prog = bpf_manager.add_program(prog)?;
             // Get the Kernel Info.
             let kernel_info = ret_prog
                 .kernel_info()
                 .expect("kernel info should be set for all loaded programs");
-            info!("Loaded static program with program id {}", kernel_info.id)
+            info!("Loaded static program with program id {}", kernel_info.id);
+            supervised.push((spec, kernel_info.id));
         }
     };
     let mut handles = vec![listeners_handle, image_manager_handle];
-    
+    if let Some(handle) = peer_server_handle {
+        handles.push(handle);
+    }
+
+    // The set of static specs currently loaded, kept in sync on SIGHUP reload.
+    let mut current_static = supervised.clone();
+
+    // Spawn a supervisor that reloads static programs that drop out, using an
+    // exponential backoff. It reaches the BpfManager through a request channel
+    // drained by the command loop below, and is told about SIGHUP reloads
+    // through `sup_ctrl_tx` so it stops supervising programs that were unloaded.
+    let (sup_tx, mut sup_rx) = mpsc::channel(32);
+    let (sup_ctrl_tx, sup_ctrl_rx) = mpsc::channel(32);
+    if !supervised.is_empty() {
+        let reconciler = ChannelReconciler::new(sup_tx);
+        let supervisor = Supervisor::new(reconciler, supervised, sup_ctrl_rx);
+        let supervisor_handle = runtime.spawn(supervisor.run());
+        handles.push(supervisor_handle);
+    } else {
+        // Nothing to supervise; drop the control receiver so a later reload's
+        // notification fails fast instead of buffering forever.
+        drop(sup_ctrl_rx);
+    }
+
     if csi_support {
         let storage_manager = StorageManager::new(tx);
         let storage_manager_handle = runtime.spawn(storage_manager.run());
         handles.push(storage_manager_handle);
     }
 
-    loop {
-        
-            _ = shutdown_handler() => {
-                info!("Signal received to stop command processing");
-                return;
+    runtime.block_on(async {
+        loop {
+            select! {
+                _ = shutdown_handler() => {
+                    info!("Signal received to stop command processing");
+                    break;
+                }
+                _ = bpf_manager.process_command() => {}
+                _ = reload_handler() => {
+                    info!("Reloading static programs from {static_program_path}");
+                    match get_static_programs(&static_program_path) {
+                        Ok(desired) => {
+                            // Diff desired against what we last loaded, keyed by
+                            // the spec's structural form so that unchanged
+                            // programs are left attached and only genuine
+                            // additions and removals move.
+                            let current_keys: Vec<String> = current_static
+                                .iter()
+                                .map(|(spec, _)| format!("{spec:?}"))
+                                .collect();
+                            let desired_keys: Vec<String> =
+                                desired.iter().map(|spec| format!("{spec:?}")).collect();
+
+                            // Unload programs that are no longer desired.
+                            let mut retained: Vec<(_, u32)> = Vec::new();
+                            for ((spec, id), key) in
+                                current_static.drain(..).zip(current_keys.into_iter())
+                            {
+                                if desired_keys.contains(&key) {
+                                    retained.push((spec, id));
+                                } else {
+                                    info!("Unloading static program with program id {id}");
+                                    if let Err(e) = bpf_manager.remove_program(id) {
+                                        error!("Failed to unload static program {id}: {e}");
+                                        retained.push((spec, id));
+                                    }
+                                }
+                            }
+                            current_static = retained;
+
+                            // Load programs that are newly desired.
+                            let loaded_keys: Vec<String> = current_static
+                                .iter()
+                                .map(|(spec, _)| format!("{spec:?}"))
+                                .collect();
+                            for spec in desired {
+                                let key = format!("{spec:?}");
+                                if loaded_keys.contains(&key) {
+                                    continue;
+                                }
+                                let retained_spec = spec.clone();
+                                match bpf_manager.add_program(spec) {
+                                    Ok(prog) => {
+                                        let id = prog
+                                            .kernel_info()
+                                            .expect("kernel info should be set for all loaded programs")
+                                            .id;
+                                        info!("Loaded static program with program id {id}");
+                                        current_static.push((retained_spec, id));
+                                    }
+                                    Err(e) => error!("Failed to load static program: {e}"),
+                                }
+                            }
+
+                            // Hand the reconciled set to the supervisor so it
+                            // supervises exactly what is now loaded — dropping
+                            // unloaded programs and picking up new ones.
+                            if let Err(e) = sup_ctrl_tx
+                                .send(SupervisorControl::SetDesired(current_static.clone()))
+                                .await
+                            {
+                                debug!("Supervisor not running, skipping reconcile: {e}");
+                            }
+                        }
+                        Err(e) => error!("Failed to re-read static programs: {e}"),
+                    }
+                }
+                Some(request) = sup_rx.recv() => {
+                    match request {
+                        SupervisorRequest::IsAlive { kernel_id, responder } => {
+                            let alive = kernel_id
+                                .map(|id| bpf_manager.get_program(id).is_ok())
+                                .unwrap_or(false);
+                            let _ = responder.send(alive);
+                        }
+                        SupervisorRequest::Reload { spec, responder } => {
+                            let result = bpf_manager
+                                .add_program(spec)
+                                .map(|prog| {
+                                    prog.kernel_info()
+                                        .expect("kernel info should be set for all loaded programs")
+                                        .id
+                                })
+                                .map_err(anyhow::Error::from);
+                            let _ = responder.send(result);
+                        }
+                    }
+                }
             }
-            _ = bpf_manager.process_command() => {}
         }
-    
+    });
+
+    Ok(())
 }
 
 pub(crate) async fn shutdown_handler() {
@@ -120,19 +307,37 @@ pub(crate) async fn shutdown_handler() {
     }
 }
 
+/// Resolves on the next `SIGHUP`, the conventional "reload configuration"
+/// signal for a long-running daemon.
+pub(crate) async fn reload_handler() {
+    let mut sighup = signal(SignalKind::hangup()).unwrap();
+    sighup.recv().await;
+    debug!("Received SIGHUP");
+}
+
 async fn serve_unix(
     path: String,
     service: BpfdServer<BpfdLoader>,
 ) -> anyhow::Result<JoinHandle<()>> {
     // Listen on Unix socket
     if Path::new(&path).exists() {
-        // Attempt to remove the socket, since bind fails if it exists
+        // Safe to remove: the caller holds the singleton daemon lock, so any
+        // existing socket is a leftover from a dead instance rather than a
+        // live peer we would be stomping.
         remove_file(&path)?;
     }
 
-    let uds = UnixListener::bind(&path)?;
+    // Bind the socket with its final permissions already in place. `bind`
+    // honours the umask, so tightening it for the duration of the call closes
+    // the window between `bind` and an explicit chmod in which the socket would
+    // otherwise be world-accessible.
+    let old_umask = umask(Mode::from_bits_truncate(!SOCK_MODE & 0o777));
+    let bound = UnixListener::bind(&path);
+    umask(old_umask);
+    let uds = bound?;
     let uds_stream = UnixListenerStream::new(uds);
-    // Always set the file permissions of our listening socket.
+    // Belt-and-suspenders: confirm the mode even if the umask interaction
+    // differs on an exotic filesystem. There is no longer an open window.
     set_file_permissions(&path.clone(), SOCK_MODE);
 
     let serve = Server::builder()
@@ -147,3 +352,57 @@ async fn serve_unix(
         info!("Shutdown Unix Handler {}", path);
     }))
 }
+
+async fn serve_tcp(
+    addr: SocketAddr,
+    tls: config::TlsConfig,
+    service: BpfdServer<BpfdLoader>,
+) -> anyhow::Result<JoinHandle<()>> {
+    // A TCP endpoint always runs over mutual TLS: the daemon presents its
+    // server certificate and only admits clients whose certificate is signed
+    // by the configured CA.
+    let server_cert = std::fs::read(&tls.cert)?;
+    let server_key = std::fs::read(&tls.key)?;
+    let ca_cert = std::fs::read(&tls.ca_cert)?;
+    let tls_config = server_tls_config(&server_cert, &server_key, &ca_cert);
+
+    let serve = Server::builder()
+        .tls_config(tls_config)?
+        .add_service(service)
+        .serve_with_shutdown(addr, shutdown_handler());
+
+    Ok(tokio::spawn(async move {
+        info!("Listening on {addr}");
+        if let Err(e) = serve.await {
+            eprintln!("Error = {e:?}");
+        }
+        info!("Shutdown TCP Handler {}", addr);
+    }))
+}
+
+async fn serve_peer(
+    addr: SocketAddr,
+    tls: config::TlsConfig,
+    service: PeerService,
+) -> anyhow::Result<JoinHandle<()>> {
+    // The peer endpoint runs over the same mutual TLS as the control endpoint,
+    // so only nodes bearing a certificate from the shared CA can advertise or
+    // fetch bytecode from us.
+    let server_cert = std::fs::read(&tls.cert)?;
+    let server_key = std::fs::read(&tls.key)?;
+    let ca_cert = std::fs::read(&tls.ca_cert)?;
+    let tls_config = server_tls_config(&server_cert, &server_key, &ca_cert);
+
+    let serve = Server::builder()
+        .tls_config(tls_config)?
+        .add_service(service.into_server())
+        .serve_with_shutdown(addr, shutdown_handler());
+
+    Ok(tokio::spawn(async move {
+        info!("Peer endpoint listening on {addr}");
+        if let Err(e) = serve.await {
+            eprintln!("Error = {e:?}");
+        }
+        info!("Shutdown Peer Handler {}", addr);
+    }))
+}
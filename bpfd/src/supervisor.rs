@@ -0,0 +1,341 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Authors of bpfd
+
+//! Supervisor for static programs.
+//!
+//! Programs loaded at startup are otherwise fire-and-forget: if a link drops
+//! or the kernel evicts a program nothing reloads it. The supervisor keeps a
+//! table of the static programs it is responsible for and periodically
+//! reconciles desired against actual state. A program found missing or in
+//! error is reloaded after an exponentially backed-off delay (base 1s,
+//! doubling on each consecutive failure up to a cap), with the backoff reset on
+//! a successful reload. A failure window guards against tight restart loops.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use log::{error, info, warn};
+use tokio::{
+    select,
+    sync::{mpsc, oneshot},
+    time::{interval, Instant},
+};
+
+/// The default reconcile interval.
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(5);
+/// The initial backoff delay.
+const BACKOFF_BASE: Duration = Duration::from_secs(1);
+/// The default backoff cap.
+const BACKOFF_CAP: Duration = Duration::from_secs(30);
+/// Once [`MAX_FAILURES_PER_WINDOW`] failures occur inside this window, the
+/// program is parked rather than restarted again, to avoid a tight crash loop.
+const FAILURE_WINDOW: Duration = Duration::from_secs(60);
+const MAX_FAILURES_PER_WINDOW: u32 = 5;
+
+/// Exponential backoff state for a single supervised program.
+#[derive(Debug, Clone)]
+struct Backoff {
+    cap: Duration,
+    consecutive_failures: u32,
+}
+
+impl Backoff {
+    fn new(cap: Duration) -> Self {
+        Self {
+            cap,
+            consecutive_failures: 0,
+        }
+    }
+
+    /// The delay to wait before the next reload attempt. Called after
+    /// [`record_failure`](Self::record_failure), so the first failure
+    /// (`consecutive_failures == 1`) yields the base delay and each subsequent
+    /// consecutive failure doubles it up to the cap.
+    fn delay(&self) -> Duration {
+        let shift = self
+            .consecutive_failures
+            .saturating_sub(1)
+            .min(u32::BITS - 1);
+        BACKOFF_BASE
+            .saturating_mul(1u32 << shift)
+            .min(self.cap)
+    }
+
+    fn record_failure(&mut self) {
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+    }
+
+    fn reset(&mut self) {
+        self.consecutive_failures = 0;
+    }
+}
+
+/// One entry in the supervisor's table.
+struct SupervisedProgram<S> {
+    spec: S,
+    last_known_kernel_id: Option<u32>,
+    backoff: Backoff,
+    next_attempt: Instant,
+    window_start: Instant,
+    failures_in_window: u32,
+}
+
+/// The operations the supervisor needs to reconcile a program. Implemented in
+/// `serve()` against the running [`BpfManager`](crate::bpf::BpfManager) command
+/// channel so the supervisor itself stays decoupled and testable.
+#[async_trait]
+pub trait Reconciler {
+    /// The static program specification the supervisor holds and replays.
+    type Spec: Clone + Send + Sync;
+
+    /// Whether the program with `kernel_id` is still loaded and healthy.
+    async fn is_alive(&self, kernel_id: Option<u32>) -> bool;
+
+    /// (Re)load `spec`, returning the new kernel id on success.
+    async fn reload(&self, spec: &Self::Spec) -> anyhow::Result<u32>;
+}
+
+/// A request the supervisor issues to the `serve()` loop, which owns the
+/// [`BpfManager`](crate::bpf::BpfManager). The loop answers each request
+/// through its `responder`.
+pub enum SupervisorRequest<S> {
+    /// Is the program with this kernel id still loaded and healthy?
+    IsAlive {
+        kernel_id: Option<u32>,
+        responder: oneshot::Sender<bool>,
+    },
+    /// Reload this static program, answering with the new kernel id.
+    Reload {
+        spec: S,
+        responder: oneshot::Sender<anyhow::Result<u32>>,
+    },
+}
+
+/// A control message the `serve()` loop sends the supervisor when the set of
+/// static programs changes, e.g. on a SIGHUP reload. Without this the
+/// supervisor would keep reloading a program that a reload has deliberately
+/// unloaded.
+pub enum SupervisorControl<S> {
+    /// Replace the supervised set with `programs`, each already loaded with the
+    /// given kernel id. Programs no longer present are dropped; newly present
+    /// ones start being supervised. Backoff and failure-window state is
+    /// preserved for programs that remain, matched by kernel id.
+    SetDesired(Vec<(S, u32)>),
+}
+
+/// A [`Reconciler`] that forwards its decisions to the `serve()` loop over a
+/// channel, mirroring the way the RPC layer reaches the `BpfManager`.
+pub struct ChannelReconciler<S> {
+    requests: mpsc::Sender<SupervisorRequest<S>>,
+}
+
+impl<S> ChannelReconciler<S> {
+    pub fn new(requests: mpsc::Sender<SupervisorRequest<S>>) -> Self {
+        Self { requests }
+    }
+}
+
+#[async_trait]
+impl<S: Clone + Send + Sync + 'static> Reconciler for ChannelReconciler<S> {
+    type Spec = S;
+
+    async fn is_alive(&self, kernel_id: Option<u32>) -> bool {
+        let (responder, rx) = oneshot::channel();
+        if self
+            .requests
+            .send(SupervisorRequest::IsAlive {
+                kernel_id,
+                responder,
+            })
+            .await
+            .is_err()
+        {
+            // The daemon is shutting down; treat as alive so we don't thrash.
+            return true;
+        }
+        rx.await.unwrap_or(true)
+    }
+
+    async fn reload(&self, spec: &Self::Spec) -> anyhow::Result<u32> {
+        let (responder, rx) = oneshot::channel();
+        self.requests
+            .send(SupervisorRequest::Reload {
+                spec: spec.clone(),
+                responder,
+            })
+            .await
+            .map_err(|_| anyhow::anyhow!("supervisor request channel closed"))?;
+        rx.await
+            .map_err(|_| anyhow::anyhow!("supervisor response channel closed"))?
+    }
+}
+
+/// Supervises the current set of static programs.
+pub struct Supervisor<R: Reconciler> {
+    reconciler: R,
+    programs: Vec<SupervisedProgram<R::Spec>>,
+    control: mpsc::Receiver<SupervisorControl<R::Spec>>,
+}
+
+impl<R: Reconciler> Supervisor<R> {
+    /// Build a supervisor for `specs`, each already loaded with the given
+    /// kernel id. `control` delivers updates to the desired set as static
+    /// programs are reloaded.
+    pub fn new(
+        reconciler: R,
+        specs: impl IntoIterator<Item = (R::Spec, u32)>,
+        control: mpsc::Receiver<SupervisorControl<R::Spec>>,
+    ) -> Self {
+        let now = Instant::now();
+        let programs = specs
+            .into_iter()
+            .map(|(spec, kernel_id)| SupervisedProgram {
+                spec,
+                last_known_kernel_id: Some(kernel_id),
+                backoff: Backoff::new(BACKOFF_CAP),
+                next_attempt: now,
+                window_start: now,
+                failures_in_window: 0,
+            })
+            .collect();
+        Self {
+            reconciler,
+            programs,
+            control,
+        }
+    }
+
+    /// Replace the supervised set with `desired`, preserving backoff and
+    /// failure-window state for programs that remain (matched by kernel id) and
+    /// dropping those no longer present so they are not reloaded.
+    fn set_desired(&mut self, desired: Vec<(R::Spec, u32)>) {
+        let now = Instant::now();
+        let mut existing = std::mem::take(&mut self.programs);
+        self.programs = desired
+            .into_iter()
+            .map(|(spec, kernel_id)| {
+                if let Some(pos) = existing
+                    .iter()
+                    .position(|p| p.last_known_kernel_id == Some(kernel_id))
+                {
+                    let mut prog = existing.remove(pos);
+                    prog.spec = spec;
+                    prog
+                } else {
+                    SupervisedProgram {
+                        spec,
+                        last_known_kernel_id: Some(kernel_id),
+                        backoff: Backoff::new(BACKOFF_CAP),
+                        next_attempt: now,
+                        window_start: now,
+                        failures_in_window: 0,
+                    }
+                }
+            })
+            .collect();
+    }
+
+    /// Run the reconcile loop until the task is cancelled.
+    pub async fn run(mut self) {
+        let mut ticker = interval(RECONCILE_INTERVAL);
+        loop {
+            select! {
+                _ = ticker.tick() => {}
+                ctrl = self.control.recv() => {
+                    match ctrl {
+                        Some(SupervisorControl::SetDesired(desired)) => self.set_desired(desired),
+                        // The serve() loop has gone away; keep supervising the
+                        // programs we already hold.
+                        None => {}
+                    }
+                    continue;
+                }
+            }
+            let now = Instant::now();
+            for program in &mut self.programs {
+                if self
+                    .reconciler
+                    .is_alive(program.last_known_kernel_id)
+                    .await
+                {
+                    program.backoff.reset();
+                    continue;
+                }
+
+                if now < program.next_attempt {
+                    continue;
+                }
+
+                // Reset the failure window if it has elapsed.
+                if now.duration_since(program.window_start) > FAILURE_WINDOW {
+                    program.window_start = now;
+                    program.failures_in_window = 0;
+                }
+                if program.failures_in_window >= MAX_FAILURES_PER_WINDOW {
+                    warn!("Static program exceeded restart budget; parking until window resets");
+                    continue;
+                }
+
+                info!("Static program missing; attempting reload");
+                match self.reconciler.reload(&program.spec).await {
+                    Ok(kernel_id) => {
+                        info!("Reloaded static program with program id {kernel_id}");
+                        program.last_known_kernel_id = Some(kernel_id);
+                        program.backoff.reset();
+                    }
+                    Err(e) => {
+                        program.backoff.record_failure();
+                        program.failures_in_window += 1;
+                        let delay = program.backoff.delay();
+                        error!("Failed to reload static program: {e}; retrying in {delay:?}");
+                        program.next_attempt = now + delay;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_starts_at_base_and_doubles() {
+        let mut b = Backoff::new(BACKOFF_CAP);
+        b.record_failure();
+        assert_eq!(b.delay(), Duration::from_secs(1));
+        b.record_failure();
+        assert_eq!(b.delay(), Duration::from_secs(2));
+        b.record_failure();
+        assert_eq!(b.delay(), Duration::from_secs(4));
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let mut b = Backoff::new(Duration::from_secs(5));
+        for _ in 0..10 {
+            b.record_failure();
+        }
+        assert_eq!(b.delay(), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn backoff_reset_returns_to_base() {
+        let mut b = Backoff::new(BACKOFF_CAP);
+        for _ in 0..4 {
+            b.record_failure();
+        }
+        b.reset();
+        b.record_failure();
+        assert_eq!(b.delay(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn backoff_shift_does_not_overflow() {
+        let mut b = Backoff::new(BACKOFF_CAP);
+        b.consecutive_failures = u32::MAX;
+        // The shift is bounded, so this must not panic and stays capped.
+        assert_eq!(b.delay(), BACKOFF_CAP);
+    }
+}
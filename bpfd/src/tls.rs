@@ -0,0 +1,40 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Authors of bpfd
+
+//! Mutual-TLS configuration for the remote gRPC endpoint.
+//!
+//! To manage bpfd over TCP we need to authenticate both ends. Remote clients
+//! present a certificate signed by a configured CA, and the daemon presents its
+//! own; only certificates that chain to that CA are accepted, so just paired
+//! clients can load programs. Pairing is therefore a CA trust decision: issuing
+//! a client a certificate from the configured CA is what admits it.
+
+use tonic::transport::{Certificate, ClientTlsConfig, Identity, ServerTlsConfig};
+
+/// Build a [`ServerTlsConfig`] that presents the daemon's server certificate
+/// and requires client certificates signed by `ca_cert_pem`.
+pub fn server_tls_config(
+    server_cert_pem: &[u8],
+    server_key_pem: &[u8],
+    ca_cert_pem: &[u8],
+) -> ServerTlsConfig {
+    let identity = Identity::from_pem(server_cert_pem, server_key_pem);
+    ServerTlsConfig::new()
+        .identity(identity)
+        .client_ca_root(Certificate::from_pem(ca_cert_pem))
+}
+
+/// Build a [`ClientTlsConfig`] that presents this node's certificate when
+/// dialling a peer and trusts peers whose certificate chains to `ca_cert_pem`.
+/// Peer-to-peer image transfers use this to authenticate both ends against the
+/// same CA as the remote control endpoint.
+pub fn client_tls_config(
+    client_cert_pem: &[u8],
+    client_key_pem: &[u8],
+    ca_cert_pem: &[u8],
+) -> ClientTlsConfig {
+    let identity = Identity::from_pem(client_cert_pem, client_key_pem);
+    ClientTlsConfig::new()
+        .identity(identity)
+        .ca_certificate(Certificate::from_pem(ca_cert_pem))
+}
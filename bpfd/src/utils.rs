@@ -47,6 +47,66 @@ pub(crate) async fn read_to_string<P: AsRef<Path>>(path: P) -> Result<String, Bp
     Ok(buffer)
 }
 
+// Atomically write `bytes` to `path` with permission bits `mode`, closing the
+// create-then-chmod window where a file is briefly world-accessible. The bytes
+// are written to a temporary file in the same directory (so the final rename
+// stays within one filesystem and is therefore atomic), the mode is applied on
+// the open file descriptor via fchmod *before* the content becomes visible, the
+// data is fsynced, and only then is the temp file renamed into place, which
+// gives crash-consistent writes. The peer subsystem uses it to admit fetched
+// blobs to the bytecode content store.
+pub(crate) async fn write_atomic(path: &str, bytes: &[u8], mode: u32) -> Result<(), BpfdError> {
+    use std::os::unix::io::AsRawFd;
+
+    use tokio::io::AsyncWriteExt;
+
+    let path = Path::new(path);
+    let dir = path
+        .parent()
+        .ok_or_else(|| BpfdError::Error(format!("{} has no parent directory", path.display())))?;
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| BpfdError::Error(format!("{} is not a file", path.display())))?;
+
+    // Keep the temporary file in the same directory as the target, hidden and
+    // tagged with the pid to avoid clashing with a concurrent writer.
+    let tmp = dir.join(format!(
+        ".{}.tmp.{}",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .custom_flags(nix::libc::O_NOCTTY)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&tmp)
+        .await
+        .map_err(|e| BpfdError::Error(format!("can't create temp file: {e}")))?;
+
+    // Apply the permission bits on the fd before the content is visible at the
+    // final path.
+    nix::sys::stat::fchmod(
+        file.as_raw_fd(),
+        nix::sys::stat::Mode::from_bits_truncate(mode),
+    )
+    .map_err(|e| BpfdError::Error(format!("can't fchmod temp file: {e}")))?;
+
+    file.write_all(bytes)
+        .await
+        .map_err(|e| BpfdError::Error(format!("can't write temp file: {e}")))?;
+    file.sync_all()
+        .await
+        .map_err(|e| BpfdError::Error(format!("can't fsync temp file: {e}")))?;
+
+    tokio::fs::rename(&tmp, path).await.map_err(|e| {
+        // Best-effort cleanup so we don't strand the temp file on failure.
+        let _ = std::fs::remove_file(&tmp);
+        BpfdError::Error(format!("can't rename temp file into place: {e}"))
+    })
+}
+
 pub(crate) fn get_ifindex(iface: &str) -> Result<u32, BpfdError> {
     match if_nametoindex(iface) {
         Ok(index) => {
@@ -60,6 +120,165 @@ pub(crate) fn get_ifindex(iface: &str) -> Result<u32, BpfdError> {
     }
 }
 
+/// The access a single ACL entry grants to a user or group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct AclAccess {
+    pub read: bool,
+    pub write: bool,
+}
+
+impl AclAccess {
+    // POSIX ACL permission bits, matching the `r`/`w`/`x` columns used by
+    // setfacl. We never grant execute on the socket or pinned maps.
+    fn perm_bits(&self) -> nix::libc::acl_perm_t {
+        let mut bits: nix::libc::acl_perm_t = 0;
+        if self.read {
+            bits |= nix::libc::ACL_READ;
+        }
+        if self.write {
+            bits |= nix::libc::ACL_WRITE;
+        }
+        bits
+    }
+}
+
+/// A principal (user or group) that an ACL entry applies to. The uid/gid are
+/// resolved from the config-driven user/group names before the entry reaches
+/// this module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AclQualifier {
+    User(u32),
+    Group(u32),
+}
+
+/// A single config-driven ACL entry: who it applies to and what they may do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct AclEntry {
+    pub qualifier: AclQualifier,
+    pub access: AclAccess,
+}
+
+// Apply a POSIX access ACL to `path` granting each entry the requested
+// read/write access, e.g. for the daemon socket or a pinned map. When the
+// backing filesystem doesn't support ACLs (`acl_set_file` fails with ENOTSUP /
+// EOPNOTSUPP), fall back to the single-group `mode` so access still works on a
+// bpffs mount without ACL support.
+pub(crate) fn set_file_acl(path: &str, entries: &[AclEntry], mode: u32) -> Result<(), BpfdError> {
+    match apply_posix_acl(path, entries) {
+        Ok(()) => Ok(()),
+        Err(nix::errno::Errno::ENOTSUP) | Err(nix::errno::Errno::EOPNOTSUPP) => {
+            warn!("ACLs unsupported on {path}, falling back to mode {mode:#o}");
+            set_file_permissions(path, mode);
+            Ok(())
+        }
+        Err(e) => Err(BpfdError::Error(format!(
+            "unable to set ACL on file {path}: {e}"
+        ))),
+    }
+}
+
+fn apply_posix_acl(path: &str, entries: &[AclEntry]) -> Result<(), nix::errno::Errno> {
+    use std::ffi::CString;
+
+    let c_path = CString::new(path).map_err(|_| nix::errno::Errno::EINVAL)?;
+
+    // SAFETY: libacl owns the returned acl_t; we release it on every path and
+    // thread the handle through every call that may reallocate it.
+    unsafe {
+        let mut acl = nix::libc::acl_init((entries.len() + 4) as nix::libc::c_int);
+        if acl.is_null() {
+            return Err(nix::errno::Errno::last());
+        }
+
+        let res = build_acl(&mut acl, entries);
+        if res.is_ok() && nix::libc::acl_valid(acl) != 0 {
+            nix::libc::acl_free(acl as *mut nix::libc::c_void);
+            return Err(nix::errno::Errno::EINVAL);
+        }
+
+        let res = res.and_then(|()| {
+            if nix::libc::acl_set_file(
+                c_path.as_ptr(),
+                nix::libc::ACL_TYPE_ACCESS,
+                acl,
+            ) == 0
+            {
+                Ok(())
+            } else {
+                Err(nix::errno::Errno::last())
+            }
+        });
+
+        nix::libc::acl_free(acl as *mut nix::libc::c_void);
+        res
+    }
+}
+
+// Populate `acl` with the mandatory owner/group/other entries plus one entry
+// per configured user/group, then compute the `ACL_MASK` that becomes
+// mandatory once named entries are present. The `acl_t` is threaded by
+// reference because `acl_create_entry` may reallocate the ACL and write back a
+// fresh handle — using a temporary would strand the caller on a stale pointer.
+// Returns the first errno encountered.
+unsafe fn build_acl(
+    acl: &mut nix::libc::acl_t,
+    entries: &[AclEntry],
+) -> Result<(), nix::errno::Errno> {
+    // Every valid access ACL must carry exactly one owner, owning-group, and
+    // other entry. The owner keeps read/write; access for everyone else is
+    // expressed through the named entries below and bounded by the computed
+    // mask, so the owning group and others are granted nothing here.
+    add_entry(acl, nix::libc::ACL_USER_OBJ, None, AclAccess { read: true, write: true })?;
+    add_entry(acl, nix::libc::ACL_GROUP_OBJ, None, AclAccess { read: false, write: false })?;
+    add_entry(acl, nix::libc::ACL_OTHER, None, AclAccess { read: false, write: false })?;
+
+    for entry in entries {
+        let (tag, id) = match entry.qualifier {
+            AclQualifier::User(uid) => (nix::libc::ACL_USER, uid),
+            AclQualifier::Group(gid) => (nix::libc::ACL_GROUP, gid),
+        };
+        add_entry(acl, tag, Some(id), entry.access)?;
+    }
+
+    // A mask is required once any named ACL_USER/ACL_GROUP entry exists; let
+    // libacl compute it from the union of the named and owning-group perms.
+    if nix::libc::acl_calc_mask(acl) != 0 {
+        return Err(nix::errno::Errno::last());
+    }
+
+    Ok(())
+}
+
+// Create one entry in `acl` with the given tag type, optional numeric
+// qualifier (set only for named user/group entries), and access bits.
+unsafe fn add_entry(
+    acl: &mut nix::libc::acl_t,
+    tag: nix::libc::acl_tag_t,
+    qualifier: Option<u32>,
+    access: AclAccess,
+) -> Result<(), nix::errno::Errno> {
+    let mut ae: nix::libc::acl_entry_t = std::ptr::null_mut();
+    if nix::libc::acl_create_entry(acl, &mut ae) != 0 {
+        return Err(nix::errno::Errno::last());
+    }
+    if nix::libc::acl_set_tag_type(ae, tag) != 0 {
+        return Err(nix::errno::Errno::last());
+    }
+    if let Some(id) = qualifier {
+        if nix::libc::acl_set_qualifier(ae, &id as *const u32 as *const nix::libc::c_void) != 0 {
+            return Err(nix::errno::Errno::last());
+        }
+    }
+
+    let mut permset: nix::libc::acl_permset_t = std::ptr::null_mut();
+    if nix::libc::acl_get_permset(ae, &mut permset) != 0 {
+        return Err(nix::errno::Errno::last());
+    }
+    nix::libc::acl_clear_perms(permset);
+    nix::libc::acl_add_perm(permset, access.perm_bits());
+    Ok(())
+}
+
 pub(crate) fn set_file_permissions(path: &str, mode: u32) {
     // Set the permissions on the file based on input
     if std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).is_err() {
@@ -67,11 +286,92 @@ pub(crate) fn set_file_permissions(path: &str, mode: u32) {
     }
 }
 
-pub(crate) fn set_dir_permissions(directory: &str, mode: u32) {
-    // Iterate through the files in the provided directory
-    for entry in std::fs::read_dir(directory).unwrap().flatten() {
-        // Set the permissions on the file based on input
-        set_file_permissions(&entry.path().into_os_string().into_string().unwrap(), mode);
+// Recursively apply `mode` to every entry beneath `directory`, descending into
+// subdirectories (e.g. per-program pin paths under the bpffs). Symlinks are
+// never followed so we can't be tricked into chmod'ing a target that lives
+// outside the bpffs tree. IO errors are collected per entry rather than
+// panicking so a single unreadable directory doesn't abort the whole walk.
+//
+// When `skip_internal_maps` is set, the `.rodata`/`.bss`/`.data` maps are left
+// untouched, matching the pinning decision made by `should_map_be_pinned`.
+pub(crate) fn set_dir_permissions(
+    directory: &str,
+    mode: u32,
+    skip_internal_maps: bool,
+) -> Result<(), BpfdError> {
+    let mut errors = Vec::new();
+    set_dir_permissions_inner(Path::new(directory), mode, skip_internal_maps, &mut errors);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(BpfdError::Error(format!(
+            "unable to set permissions on {} entrie(s) under {directory}: {}",
+            errors.len(),
+            errors.join("; ")
+        )))
+    }
+}
+
+fn set_dir_permissions_inner(
+    directory: &Path,
+    mode: u32,
+    skip_internal_maps: bool,
+    errors: &mut Vec<String>,
+) {
+    let entries = match std::fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(e) => {
+            errors.push(format!("can't read dir {}: {e}", directory.display()));
+            return;
+        }
+    };
+
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                errors.push(format!("can't read entry in {}: {e}", directory.display()));
+                continue;
+            }
+        };
+        let path = entry.path();
+
+        // Use symlink_metadata so we inspect the link itself rather than its
+        // target and never follow it.
+        let metadata = match std::fs::symlink_metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                errors.push(format!("can't stat {}: {e}", path.display()));
+                continue;
+            }
+        };
+        let file_type = metadata.file_type();
+
+        if file_type.is_symlink() {
+            continue;
+        }
+
+        // Only regular map files can be internal maps, so the skip is scoped to
+        // non-directories; a subdirectory that happens to share an internal-map
+        // name is still descended into below. The name is matched on a lossy
+        // view so a non-UTF-8 filename is evaluated by `should_map_be_pinned`
+        // rather than slipping through chmod'd-but-unexamined.
+        let is_skippable_map = skip_internal_maps
+            && !file_type.is_dir()
+            && path
+                .file_name()
+                .map(|n| !should_map_be_pinned(&n.to_string_lossy()))
+                .unwrap_or(false);
+
+        if !is_skippable_map {
+            if let Err(e) = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)) {
+                errors.push(format!("can't set permissions on {}: {e}", path.display()));
+            }
+        }
+
+        if file_type.is_dir() {
+            set_dir_permissions_inner(&path, mode, skip_internal_maps, errors);
+        }
     }
 }
 
@@ -0,0 +1,122 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Authors of bpfman
+
+//! Per-program authorization.
+//!
+//! The schema stores rich per-program metadata but has no notion of *who* may
+//! load, attach, unload, or access the maps of a given program. This module
+//! records, per `prog_id`, the owning uid/gid and an allowed-capability set,
+//! and [`ProgramAuthorization::authorize`] checks the calling client's
+//! credentials (obtained from the unix socket peer via `SO_PEERCRED`) against
+//! it. The module does not hook into the RPC handlers: a policy is enforced
+//! only where a caller runs `authorize` before the operation.
+
+use std::os::unix::net::UnixStream;
+
+use bitflags::bitflags;
+use diesel::{Identifiable, Insertable, Queryable, Selectable};
+use thiserror::Error;
+
+use crate::schema::program_authorization;
+
+bitflags! {
+    /// The set of actions a client may perform against a program. Stored as a
+    /// single integer column alongside the owning uid/gid.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Capabilities: i32 {
+        /// May attach the program to a hook.
+        const ATTACH = 1 << 0;
+        /// May detach the program from a hook.
+        const DETACH = 1 << 1;
+        /// May unload the program entirely.
+        const UNLOAD = 1 << 2;
+        /// May read the program's maps.
+        const MAP_READ = 1 << 3;
+        /// May write the program's maps.
+        const MAP_WRITE = 1 << 4;
+    }
+}
+
+/// The stored access policy for a single program.
+#[derive(Queryable, Identifiable, Insertable, Selectable, Debug, PartialEq, Clone)]
+#[diesel(table_name = program_authorization)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct ProgramAuthorization {
+    pub id: i32,
+    pub prog_id: i32,
+    pub owner_uid: i32,
+    pub owner_gid: i32,
+    pub capabilities: i32,
+}
+
+impl ProgramAuthorization {
+    /// The capability set granted to non-owner clients.
+    pub fn capabilities(&self) -> Capabilities {
+        Capabilities::from_bits_truncate(self.capabilities)
+    }
+}
+
+/// The credentials of a connected client, as reported by `SO_PEERCRED`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerCredentials {
+    pub uid: u32,
+    pub gid: u32,
+    pub pid: Option<i32>,
+}
+
+impl PeerCredentials {
+    /// Read the peer credentials of a connected unix socket.
+    pub fn from_unix_stream(stream: &UnixStream) -> Result<Self, AuthorizationError> {
+        let cred = stream
+            .peer_cred()
+            .map_err(|e| AuthorizationError::PeerCredentials(e.to_string()))?;
+        Ok(Self {
+            uid: cred.uid,
+            gid: cred.gid,
+            pid: cred.pid,
+        })
+    }
+}
+
+/// Returned when a client is not permitted to perform the requested action.
+#[derive(Debug, Error)]
+pub enum AuthorizationError {
+    #[error("unable to read peer credentials: {0}")]
+    PeerCredentials(String),
+
+    #[error(
+        "uid {uid} is not permitted to {capability:?} program {prog_id} owned by uid {owner_uid}"
+    )]
+    Denied {
+        prog_id: u32,
+        uid: u32,
+        owner_uid: u32,
+        capability: Capabilities,
+    },
+}
+
+impl ProgramAuthorization {
+    /// Check whether `client` may perform `capability` on this program. The
+    /// owner always has full access; everyone else is constrained to the
+    /// stored capability set.
+    pub fn authorize(
+        &self,
+        client: &PeerCredentials,
+        capability: Capabilities,
+    ) -> Result<(), AuthorizationError> {
+        if client.uid == self.owner_uid as u32 {
+            return Ok(());
+        }
+
+        if self.capabilities().contains(capability) {
+            return Ok(());
+        }
+
+        Err(AuthorizationError::Denied {
+            prog_id: self.prog_id as u32,
+            uid: client.uid,
+            owner_uid: self.owner_uid as u32,
+            capability,
+        })
+    }
+}
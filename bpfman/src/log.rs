@@ -0,0 +1,277 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Authors of bpfman
+
+//! Built-in eBPF log collection.
+//!
+//! Programs loaded through bpfman frequently use the `aya-log` convention: a
+//! perf-event-array or ringbuf map (conventionally named `AYA_LOGS`) into which
+//! `aya-log-common` records are written. This module decodes that wire format
+//! into structured records so operators can tail a program's logs without
+//! writing their own userspace reader.
+//!
+//! The decoded level is mapped onto the daemon's `log` crate levels, and the
+//! records are surfaced over a streaming gRPC endpoint (`bpfman log <id>` on the
+//! CLI) keyed on the program id. The log map's kernel map id is recorded in the
+//! existing `Map`/`maps_to_programs` tables at load time so it can be
+//! rediscovered on demand.
+
+use crate::errors::BpfmanError;
+
+/// The aya-log-common log level, as written in the record header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl TryFrom<u8> for Level {
+    type Error = BpfmanError;
+
+    fn try_from(value: u8) -> Result<Self, <Self as TryFrom<u8>>::Error> {
+        Ok(match value {
+            1 => Level::Error,
+            2 => Level::Warn,
+            3 => Level::Info,
+            4 => Level::Debug,
+            5 => Level::Trace,
+            other => {
+                return Err(BpfmanError::Error(format!(
+                    "invalid aya-log level {other}"
+                )))
+            }
+        })
+    }
+}
+
+impl From<Level> for log::Level {
+    fn from(value: Level) -> Self {
+        match value {
+            Level::Error => log::Level::Error,
+            Level::Warn => log::Level::Warn,
+            Level::Info => log::Level::Info,
+            Level::Debug => log::Level::Debug,
+            Level::Trace => log::Level::Trace,
+        }
+    }
+}
+
+/// The kind of a single `RecordField` in the wire format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFieldKind {
+    Target = 1,
+    Level,
+    Module,
+    File,
+    Line,
+    NumArgs,
+    Log,
+    DisplayHint,
+}
+
+impl TryFrom<u8> for RecordFieldKind {
+    type Error = BpfmanError;
+
+    fn try_from(value: u8) -> Result<Self, <Self as TryFrom<u8>>::Error> {
+        Ok(match value {
+            1 => RecordFieldKind::Target,
+            2 => RecordFieldKind::Level,
+            3 => RecordFieldKind::Module,
+            4 => RecordFieldKind::File,
+            5 => RecordFieldKind::Line,
+            6 => RecordFieldKind::NumArgs,
+            7 => RecordFieldKind::Log,
+            8 => RecordFieldKind::DisplayHint,
+            other => {
+                return Err(BpfmanError::Error(format!(
+                    "invalid aya-log record field {other}"
+                )))
+            }
+        })
+    }
+}
+
+/// The formatting directive applied to a logged argument.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayHint {
+    Default,
+    LowerHex,
+    UpperHex,
+    Ipv4,
+    Ipv6,
+    LowerMac,
+    UpperMac,
+}
+
+impl TryFrom<u8> for DisplayHint {
+    type Error = BpfmanError;
+
+    fn try_from(value: u8) -> Result<Self, <Self as TryFrom<u8>>::Error> {
+        Ok(match value {
+            0 => DisplayHint::Default,
+            1 => DisplayHint::LowerHex,
+            2 => DisplayHint::UpperHex,
+            3 => DisplayHint::Ipv4,
+            4 => DisplayHint::Ipv6,
+            5 => DisplayHint::LowerMac,
+            6 => DisplayHint::UpperMac,
+            other => {
+                return Err(BpfmanError::Error(format!(
+                    "invalid aya-log display hint {other}"
+                )))
+            }
+        })
+    }
+}
+
+/// A decoded log record ready to be re-emitted over the gRPC stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogRecord {
+    pub level: Level,
+    pub target: Option<String>,
+    pub module: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub message: String,
+}
+
+/// Decode a single `aya-log-common` record out of a per-CPU buffer.
+///
+/// The wire format is a sequence of length-prefixed fields: each field is a
+/// `RecordFieldKind` tag byte, a little-endian `u16` length, then that many
+/// value bytes. String fields carry UTF-8 bytes; the `Log` field carries the
+/// already-formatted message and the `DisplayHint` field carries a single byte.
+pub fn decode_record(buf: &[u8]) -> Result<LogRecord, BpfmanError> {
+    let mut level = None;
+    let mut target = None;
+    let mut module = None;
+    let mut file = None;
+    let mut line = None;
+    let mut message = String::new();
+
+    let mut cursor = 0usize;
+    while cursor < buf.len() {
+        let kind = RecordFieldKind::try_from(buf[cursor])?;
+        cursor += 1;
+
+        let len = read_u16(buf, cursor)? as usize;
+        cursor += 2;
+
+        let value = buf
+            .get(cursor..cursor + len)
+            .ok_or_else(|| BpfmanError::Error("truncated aya-log record".to_string()))?;
+        cursor += len;
+
+        match kind {
+            RecordFieldKind::Level => level = Some(Level::try_from(first(value)?)?),
+            RecordFieldKind::Target => target = Some(decode_string(value)?),
+            RecordFieldKind::Module => module = Some(decode_string(value)?),
+            RecordFieldKind::File => file = Some(decode_string(value)?),
+            RecordFieldKind::Line => line = Some(read_u32(value)?),
+            RecordFieldKind::Log => message = decode_string(value)?,
+            // NumArgs and DisplayHint shape how the producer encodes the
+            // message; the formatted text arrives in the Log field so we have
+            // nothing further to accumulate here.
+            RecordFieldKind::NumArgs | RecordFieldKind::DisplayHint => {}
+        }
+    }
+
+    Ok(LogRecord {
+        level: level.ok_or_else(|| BpfmanError::Error("aya-log record has no level".to_string()))?,
+        target,
+        module,
+        file,
+        line,
+        message,
+    })
+}
+
+fn first(value: &[u8]) -> Result<u8, BpfmanError> {
+    value
+        .first()
+        .copied()
+        .ok_or_else(|| BpfmanError::Error("empty aya-log field".to_string()))
+}
+
+fn read_u16(buf: &[u8], at: usize) -> Result<u16, BpfmanError> {
+    let bytes = buf
+        .get(at..at + 2)
+        .ok_or_else(|| BpfmanError::Error("truncated aya-log length".to_string()))?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(value: &[u8]) -> Result<u32, BpfmanError> {
+    let bytes: [u8; 4] = value
+        .try_into()
+        .map_err(|_| BpfmanError::Error("invalid aya-log u32 field".to_string()))?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn decode_string(value: &[u8]) -> Result<String, BpfmanError> {
+    String::from_utf8(value.to_vec())
+        .map_err(|e| BpfmanError::Error(format!("invalid utf-8 in aya-log field: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Append a length-prefixed field: kind tag, little-endian `u16` length,
+    /// then the value bytes.
+    fn push_field(buf: &mut Vec<u8>, kind: RecordFieldKind, value: &[u8]) {
+        buf.push(kind as u8);
+        buf.extend_from_slice(&(value.len() as u16).to_le_bytes());
+        buf.extend_from_slice(value);
+    }
+
+    #[test]
+    fn decodes_all_fields_and_advances_cursor() {
+        let mut buf = Vec::new();
+        push_field(&mut buf, RecordFieldKind::Level, &[3]); // Info
+        push_field(&mut buf, RecordFieldKind::Target, b"my_prog");
+        push_field(&mut buf, RecordFieldKind::Module, b"my_prog::inner");
+        push_field(&mut buf, RecordFieldKind::File, b"src/main.rs");
+        push_field(&mut buf, RecordFieldKind::Line, &42u32.to_le_bytes());
+        push_field(&mut buf, RecordFieldKind::Log, b"hello world");
+
+        let record = decode_record(&buf).expect("record decodes");
+        assert_eq!(record.level, Level::Info);
+        assert_eq!(record.target.as_deref(), Some("my_prog"));
+        assert_eq!(record.module.as_deref(), Some("my_prog::inner"));
+        assert_eq!(record.file.as_deref(), Some("src/main.rs"));
+        assert_eq!(record.line, Some(42));
+        assert_eq!(record.message, "hello world");
+    }
+
+    #[test]
+    fn skips_num_args_and_display_hint_fields() {
+        let mut buf = Vec::new();
+        push_field(&mut buf, RecordFieldKind::Level, &[1]); // Error
+        push_field(&mut buf, RecordFieldKind::NumArgs, &[2]);
+        push_field(&mut buf, RecordFieldKind::DisplayHint, &[1]);
+        push_field(&mut buf, RecordFieldKind::Log, b"boom");
+
+        let record = decode_record(&buf).expect("record decodes");
+        assert_eq!(record.level, Level::Error);
+        assert_eq!(record.message, "boom");
+        assert_eq!(record.target, None);
+    }
+
+    #[test]
+    fn missing_level_is_rejected() {
+        let mut buf = Vec::new();
+        push_field(&mut buf, RecordFieldKind::Log, b"no level");
+        assert!(decode_record(&buf).is_err());
+    }
+
+    #[test]
+    fn truncated_value_is_rejected() {
+        // Field claims 8 bytes but only 2 follow.
+        let mut buf = vec![RecordFieldKind::Target as u8];
+        buf.extend_from_slice(&8u16.to_le_bytes());
+        buf.extend_from_slice(b"hi");
+        assert!(decode_record(&buf).is_err());
+    }
+}
@@ -4,8 +4,10 @@
 //! Commands between the RPC thread and the BPF thread
 use std::{
     collections::HashMap,
+    ffi::OsString,
     fmt, fs,
     num::NonZeroU32,
+    os::unix::ffi::{OsStrExt, OsStringExt},
     path::{Path, PathBuf},
     time::SystemTime,
 };
@@ -61,6 +63,10 @@ pub struct BytecodeImage {
     pub image_pull_policy: ImagePullPolicy,
     pub username: Option<String>,
     pub password: Option<String>,
+    /// An optional `sha256:<digest>` the image is pinned to. When set, the
+    /// pulled manifest/layer digest is verified against it before the bytecode
+    /// is extracted, giving reproducible, tamper-evident loads.
+    pub digest: Option<String>,
 }
 
 impl BytecodeImage {
@@ -70,6 +76,13 @@ impl BytecodeImage {
         username: Option<String>,
         password: Option<String>,
     ) -> Self {
+        // Split off a trailing `@sha256:...` so the digest can be verified
+        // separately from the repository reference used to pull.
+        let (image_url, digest) = match image_url.split_once("@sha256:") {
+            Some((url, digest)) => (url.to_string(), Some(format!("sha256:{digest}"))),
+            None => (image_url, None),
+        };
+
         Self {
             image_url,
             image_pull_policy: image_pull_policy
@@ -77,6 +90,7 @@ impl BytecodeImage {
                 .expect("Unable to parse ImagePullPolicy"),
             username,
             password,
+            digest,
         }
     }
 
@@ -93,6 +107,7 @@ pub struct ListFilter {
     pub(crate) program_type: Option<u32>,
     pub(crate) metadata_selector: HashMap<String, String>,
     pub(crate) bpfman_programs_only: bool,
+    pub(crate) attachment_state: AttachmentState,
 }
 
 impl ListFilter {
@@ -100,11 +115,13 @@ impl ListFilter {
         program_type: Option<u32>,
         metadata_selector: HashMap<String, String>,
         bpfman_programs_only: bool,
+        attachment_state: AttachmentState,
     ) -> Self {
         Self {
             program_type,
             metadata_selector,
             bpfman_programs_only,
+            attachment_state,
         }
     }
 
@@ -161,6 +178,15 @@ impl ListFilter {
                 }
             }
         }
+
+        // Filter on attachment state if the caller asked for it.
+        match self.attachment_state {
+            AttachmentState::Any => {}
+            AttachmentState::Attached if !program.attached() => return false,
+            AttachmentState::LoadedOnly if program.attached() => return false,
+            _ => {}
+        }
+
         true
     }
 }
@@ -204,6 +230,70 @@ pub enum Program {
     /// functions in user-space binaries.
     Uprobe(UprobeProgram),
 
+    /// A USDT (User Statically-Defined Tracing) program.
+    ///
+    /// USDT programs attach to statically-defined tracepoints embedded
+    /// in a user-space binary or library, identified by a provider and
+    /// probe name rather than a raw offset. They are a specialised form
+    /// of uprobe resolved through the target ELF's `.note.stapsdt`
+    /// entries.
+    Usdt(UsdtProgram),
+
+    /// A PerfEvent program.
+    ///
+    /// PerfEvent programs are sampling profilers attached via
+    /// `perf_event_open`. They sample a PMU counter (hardware, software,
+    /// or raw) at a configured frequency or period across one or all
+    /// online CPUs, typically to collect kernel/user stack traces.
+    PerfEvent(PerfEventProgram),
+
+    /// A stream-parser / stream-verdict (`SkSkb`) program.
+    ///
+    /// SkSkb programs are attached to a sockmap or sockhash and drive
+    /// socket redirection: a stream parser delimits messages and a
+    /// stream verdict decides where they go. The kernel collapses both
+    /// into one program type, so the sub-kind is stored separately.
+    SkSkb(SkSkbProgram),
+
+    /// An `SkMsg` program.
+    ///
+    /// SkMsg programs are attached to a sockmap or sockhash and run on
+    /// `sendmsg` to steer socket messages, completing the socket
+    /// redirection pipeline alongside `SkSkb`.
+    SkMsg(SkMsgProgram),
+
+    /// A `sock_ops` program.
+    ///
+    /// Attached to a cgroup v2 directory, `sock_ops` programs run on TCP
+    /// socket lifecycle events and can tune per-connection options such as
+    /// the initial congestion window or SYN RTO.
+    CgroupSockOps(CgroupProgram),
+
+    /// A `cgroup_skb` program.
+    ///
+    /// Attached to a cgroup v2 directory on the ingress or egress hook to
+    /// filter or account for packets belonging to the cgroup's sockets.
+    CgroupSkb(CgroupProgram),
+
+    /// A `cgroup_sock` program.
+    ///
+    /// Attached to a cgroup v2 directory, `cgroup_sock` programs run on
+    /// socket creation/release and post-bind events for the cgroup.
+    CgroupSock(CgroupProgram),
+
+    /// A `cgroup_sock_addr` program.
+    ///
+    /// Attached to a cgroup v2 directory to intercept and rewrite socket
+    /// addresses on operations such as `connect`, `bind`, `sendmsg`, and
+    /// `recvmsg`.
+    CgroupSockAddr(CgroupProgram),
+
+    /// A `cgroup_sockopt` program.
+    ///
+    /// Attached to a cgroup v2 directory to intercept `getsockopt` and
+    /// `setsockopt` for the cgroup's sockets.
+    CgroupSockopt(CgroupProgram),
+
     /// An Fentry (Function Entry) program.
     ///
     /// Fentry programs are a type of BPF program that are attached to
@@ -229,6 +319,14 @@ pub enum Program {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum Location {
     Image(BytecodeImage),
+    /// Bytecode supplied as a local OCI image archive: a path to an on-disk
+    /// tar or `oci-layout` directory. This lets air-gapped hosts load programs
+    /// without registry access. The optional digest pins and verifies the
+    /// archive's content before extraction.
+    ImageArchive {
+        path: String,
+        digest: Option<String>,
+    },
     File(String),
 }
 
@@ -240,7 +338,7 @@ impl Location {
         match self {
             Location::File(l) => Ok((crate::utils::read(l)?, Vec::new())),
             Location::Image(l) => {
-                let (path, bpf_function_names) = image_manager
+                let (path, bpf_function_names, digest) = image_manager
                     .get_image(
                         root_db,
                         &l.image_url,
@@ -249,11 +347,48 @@ impl Location {
                         l.password.clone(),
                     )
                     .await?;
+                // Reject a pulled image whose digest doesn't match the pin.
+                self.verify(&digest)?;
                 let bytecode = image_manager.get_bytecode_from_image_store(root_db, path)?;
 
                 Ok((bytecode, bpf_function_names))
             }
+            Location::ImageArchive { path, .. } => {
+                let (store_path, bpf_function_names, computed_digest) =
+                    image_manager.get_image_from_archive(root_db, path).await?;
+                // The same pinned-digest check as the registry path.
+                self.verify(&computed_digest)?;
+                let bytecode =
+                    image_manager.get_bytecode_from_image_store(root_db, store_path)?;
+                Ok((bytecode, bpf_function_names))
+            }
+        }
+    }
+
+    /// The `sha256:<digest>` this location is pinned to, if any. A `File`
+    /// location is never digest-pinned.
+    pub fn pinned_digest(&self) -> Option<&str> {
+        match self {
+            Location::Image(i) => i.digest.as_deref(),
+            Location::ImageArchive { digest, .. } => digest.as_deref(),
+            Location::File(_) => None,
+        }
+    }
+
+    /// Verify a pulled image's computed digest against the pinned one. This is
+    /// the single digest check for both the registry (`Image`) and local
+    /// archive (`ImageArchive`) paths, so the two cannot drift. When the
+    /// location is not digest-pinned it is a no-op; otherwise a mismatch is
+    /// rejected so a mutated tag or tampered archive never loads.
+    pub fn verify(&self, pulled_digest: &str) -> Result<(), BpfmanError> {
+        if let Some(expected) = self.pinned_digest() {
+            if expected != pulled_digest {
+                return Err(BpfmanError::Error(format!(
+                    "image digest mismatch: expected {expected}, got {pulled_digest}"
+                )));
+            }
         }
+        Ok(())
     }
 }
 
@@ -368,6 +503,17 @@ pub struct TcProgramData {
     proceed_on: String,
 }
 
+#[derive(Queryable, Identifiable, Insertable, Selectable, Debug, PartialEq)]
+#[diesel(table_name = cgroup_program_data)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(belongs_to(ProgramData, foreign_key = prog_id))]
+pub struct CgroupProgramData {
+    id: u32,
+    prog_id: u32,
+    cgroup_path: String,
+    attach_type: i32,
+}
+
 #[derive(Queryable, Identifiable, Selectable, Debug, PartialEq)]
 #[diesel(table_name = tracepoint_program_data)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
@@ -406,6 +552,38 @@ pub struct UprobeProgramData {
     target: String,
 }
 
+#[derive(Queryable, Identifiable, Insertable, Selectable, Debug, PartialEq)]
+#[diesel(table_name = perf_event_program_data)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(belongs_to(ProgramData, foreign_key = prog_id))]
+pub struct PerfEventProgramData {
+    id: u32,
+    prog_id: u32,
+    pmu_type: u32,
+    pmu_config: u64,
+    sample_frequency: Option<u64>,
+    sample_period: Option<u64>,
+    cpu: Option<i32>,
+    attached: bool,
+}
+
+#[derive(Queryable, Identifiable, Insertable, Selectable, Debug, PartialEq)]
+#[diesel(table_name = usdt_program_data)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(belongs_to(ProgramData, foreign_key = prog_id))]
+pub struct UsdtProgramData {
+    id: u32,
+    prog_id: u32,
+    target: String,
+    provider: String,
+    probe: String,
+    cookie: Option<u64>,
+    pid: Option<i32>,
+    container_pid: Option<i32>,
+    offset: u64,
+    semaphore_addr: u64,
+}
+
 #[derive(Queryable, Identifiable, Insertable, Selectable, Debug, PartialEq)]
 #[diesel(table_name = fentry_program_data)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
@@ -469,6 +647,40 @@ pub struct MapsToPrograms {
     prog_id: u32,
 }
 
+/// A single attachment of a loaded program to a hook.
+///
+/// Attachment state used to be baked into the `XdpProgramData`/`TcProgramData`
+/// row, tying a loaded program to exactly one hook. Splitting it into its own
+/// table lets a loaded program be attached to several interfaces at once and
+/// lets `detach` tear down one hook while the bytecode stays loaded, mirroring
+/// aya's load/attach split where attaching yields a distinct link id.
+#[derive(Queryable, Identifiable, Insertable, Selectable, Debug, PartialEq, Clone)]
+#[diesel(table_name = attachments)]
+#[diesel(primary_key(attachment_id))]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+#[diesel(belongs_to(ProgramData, foreign_key = prog_id))]
+pub struct Attachment {
+    pub attachment_id: i32,
+    pub prog_id: i32,
+    pub iface: Option<String>,
+    pub if_index: Option<i32>,
+    pub direction: Option<String>,
+    pub priority: Option<i32>,
+    pub current_position: Option<i32>,
+}
+
+/// Filters a program list by whether it currently has any attachment.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AttachmentState {
+    /// Match regardless of attachment state.
+    #[default]
+    Any,
+    /// Match only programs with at least one live attachment.
+    Attached,
+    /// Match only programs that are loaded but not attached to any hook.
+    LoadedOnly,
+}
+
 impl ProgramData {
     /// Creates a new `ProgramData` instance.
     ///
@@ -670,6 +882,16 @@ impl ProgramData {
                 if let Some(p) = l.password {
                     sled_insert(&self.db_tree, LOCATION_PASSWORD, p.as_bytes())?;
                 };
+                if let Some(d) = l.digest {
+                    sled_insert(&self.db_tree, LOCATION_IMAGE_DIGEST, d.as_bytes())?;
+                };
+                Ok(())
+            }
+            Location::ImageArchive { path, digest } => {
+                sled_insert(&self.db_tree, LOCATION_IMAGE_ARCHIVE, path.as_bytes())?;
+                if let Some(d) = digest {
+                    sled_insert(&self.db_tree, LOCATION_IMAGE_DIGEST, d.as_bytes())?;
+                };
                 Ok(())
             }
         }
@@ -697,6 +919,12 @@ impl ProgramData {
     pub fn get_location(&self) -> Result<Location, BpfmanError> {
         if let Ok(l) = sled_get(&self.db_tree, LOCATION_FILENAME) {
             Ok(Location::File(bytes_to_string(&l).to_string()))
+        } else if let Some(p) = sled_get_option(&self.db_tree, LOCATION_IMAGE_ARCHIVE)? {
+            Ok(Location::ImageArchive {
+                path: bytes_to_string(&p),
+                digest: sled_get_option(&self.db_tree, LOCATION_IMAGE_DIGEST)?
+                    .map(|v| bytes_to_string(&v)),
+            })
         } else {
             Ok(Location::Image(BytecodeImage {
                 image_url: bytes_to_string(&sled_get(&self.db_tree, LOCATION_IMAGE_URL)?)
@@ -712,6 +940,8 @@ impl ProgramData {
                     .map(|v| bytes_to_string(&v)),
                 password: sled_get_option(&self.db_tree, LOCATION_PASSWORD)?
                     .map(|v| bytes_to_string(&v)),
+                digest: sled_get_option(&self.db_tree, LOCATION_IMAGE_DIGEST)?
+                    .map(|v| bytes_to_string(&v)),
             }))
         }
     }
@@ -828,11 +1058,9 @@ impl ProgramData {
     }
 
     pub(crate) fn set_map_pin_path(&mut self, path: &Path) -> Result<(), BpfmanError> {
-        sled_insert(
-            &self.db_tree,
-            MAP_PIN_PATH,
-            path.to_str().unwrap().as_bytes(),
-        )
+        // Persist the raw OS bytes rather than a UTF-8 string so a non-UTF-8
+        // pin path doesn't panic here.
+        sled_insert(&self.db_tree, MAP_PIN_PATH, path.as_os_str().as_bytes())
     }
 
     /// Retrieves the map pin path.
@@ -846,8 +1074,8 @@ impl ProgramData {
     /// This function will return an error if:
     /// - There is an issue fetching the map pin path from the database.
     pub fn get_map_pin_path(&self) -> Result<Option<PathBuf>, BpfmanError> {
-        sled_get_option(&self.db_tree, MAP_PIN_PATH)
-            .map(|v| v.map(|f| PathBuf::from(bytes_to_string(&f))))
+        Ok(sled_get_option(&self.db_tree, MAP_PIN_PATH)?
+            .map(|f| PathBuf::from(bytes_to_os_string(&f))))
     }
 
     // set_maps_used_by differs from other setters in that it's explicitly idempotent.
@@ -896,6 +1124,50 @@ impl ProgramData {
         });
     }
 
+    /// Add `id` to the set of programs borrowing this (owner) program's map.
+    /// The used-by set acts as a refcount: a map's pin is only removed once the
+    /// last borrower is gone.
+    pub(crate) fn add_map_user(&mut self, id: u32) -> Result<(), BpfmanError> {
+        let mut users = self.get_maps_used_by()?;
+        if !users.contains(&id) {
+            users.push(id);
+            self.set_maps_used_by(users)?;
+        }
+        Ok(())
+    }
+
+    /// Remove `id` from the used-by set. When the set becomes empty the pinned
+    /// map is unpinned from disk and the owner entry removed, so a shared map's
+    /// pin doesn't leak once no program borrows it. Returns `true` when the
+    /// last user was removed.
+    pub(crate) fn remove_map_user(&mut self, id: u32) -> Result<bool, BpfmanError> {
+        let mut users = self.get_maps_used_by()?;
+        users.retain(|u| *u != id);
+        let emptied = users.is_empty();
+        self.set_maps_used_by(users)?;
+
+        if emptied {
+            if let Some(path) = self.get_map_pin_path()? {
+                if path.exists() {
+                    fs::remove_dir_all(&path).map_err(|e| {
+                        BpfmanError::DatabaseError(
+                            format!("unable to unpin shared map at {}", path.display()),
+                            e.to_string(),
+                        )
+                    })?;
+                }
+            }
+            self.db_tree
+                .remove(MAP_OWNER_ID)
+                .map_err(|e| BpfmanError::DatabaseError(
+                    "unable to clear map owner id".to_string(),
+                    e.to_string(),
+                ))?;
+        }
+
+        Ok(emptied)
+    }
+
     pub(crate) fn get_program_bytes(&self) -> Result<Vec<u8>, BpfmanError> {
         sled_get(&self.db_tree, PROGRAM_BYTES)
     }
@@ -1337,7 +1609,7 @@ impl UprobeProgram {
         if let Some(p) = pid {
             uprobe_prog.set_pid(p)?;
         }
-        uprobe_prog.set_target(target)?;
+        uprobe_prog.set_target(target.as_ref())?;
         uprobe_prog.get_data_mut().set_kind(ProgramType::Probe)?;
         Ok(uprobe_prog)
     }
@@ -1392,12 +1664,300 @@ impl UprobeProgram {
         Ok(sled_get_option(&self.data.db_tree, UPROBE_PID)?.map(bytes_to_i32))
     }
 
-    pub(crate) fn set_target(&mut self, target: String) -> Result<(), BpfmanError> {
+    pub(crate) fn set_target(&mut self, target: &std::ffi::OsStr) -> Result<(), BpfmanError> {
+        // Persist the raw OS bytes so a uprobe can target a file whose name is
+        // not valid UTF-8.
         sled_insert(&self.data.db_tree, UPROBE_TARGET, target.as_bytes())
     }
 
+    pub fn get_target(&self) -> Result<OsString, BpfmanError> {
+        sled_get(&self.data.db_tree, UPROBE_TARGET).map(|v| bytes_to_os_string(&v))
+    }
+
+    pub(crate) fn get_data(&self) -> &ProgramData {
+        &self.data
+    }
+
+    pub(crate) fn get_data_mut(&mut self) -> &mut ProgramData {
+        &mut self.data
+    }
+}
+
+/// The PMU a perf event samples, mirroring `perf_type_id` in the kernel's
+/// `perf_event_open` ABI. The `config` selects the specific counter (e.g.
+/// `PERF_COUNT_HW_CPU_CYCLES`) or, for `Raw`, an encoded raw event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PerfPmuType {
+    Hardware,
+    Software,
+    Raw,
+}
+
+impl From<PerfPmuType> for u32 {
+    fn from(value: PerfPmuType) -> Self {
+        match value {
+            PerfPmuType::Hardware => 0,
+            PerfPmuType::Software => 1,
+            PerfPmuType::Raw => 4,
+        }
+    }
+}
+
+impl TryFrom<u32> for PerfPmuType {
+    type Error = ParseError;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => PerfPmuType::Hardware,
+            1 => PerfPmuType::Software,
+            4 => PerfPmuType::Raw,
+            other => {
+                return Err(ParseError::InvalidProgramType {
+                    program: format!("perf pmu type {other}"),
+                })
+            }
+        })
+    }
+}
+
+/// How often a perf event fires: either a target rate in samples/sec, or a
+/// fixed period of every N events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PerfSamplePolicy {
+    Frequency(u64),
+    Period(u64),
+}
+
+/// Which CPUs a perf event is attached to. `AllCpus` expands to one attachment
+/// per online CPU at load time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PerfScope {
+    Cpu(i32),
+    AllCpus,
+}
+
+#[derive(Debug, Clone)]
+pub struct PerfEventProgram {
+    pub(crate) data: ProgramData,
+}
+
+impl PerfEventProgram {
+    pub fn new(
+        data: ProgramData,
+        pmu_type: PerfPmuType,
+        pmu_config: u64,
+        sample_policy: PerfSamplePolicy,
+        scope: PerfScope,
+    ) -> Result<Self, BpfmanError> {
+        let mut perf_prog = Self { data };
+
+        perf_prog.set_pmu_type(pmu_type)?;
+        perf_prog.set_pmu_config(pmu_config)?;
+        perf_prog.set_sample_policy(sample_policy)?;
+        perf_prog.set_scope(scope)?;
+        perf_prog.get_data_mut().set_kind(ProgramType::PerfEvent)?;
+
+        Ok(perf_prog)
+    }
+
+    pub(crate) fn set_pmu_type(&mut self, pmu_type: PerfPmuType) -> Result<(), BpfmanError> {
+        sled_insert(
+            &self.data.db_tree,
+            PERF_EVENT_PMU_TYPE,
+            &Into::<u32>::into(pmu_type).to_ne_bytes(),
+        )
+    }
+
+    pub fn get_pmu_type(&self) -> Result<PerfPmuType, BpfmanError> {
+        let value = bytes_to_u32(sled_get(&self.data.db_tree, PERF_EVENT_PMU_TYPE)?);
+        PerfPmuType::try_from(value).map_err(|e| {
+            BpfmanError::DatabaseError("Failed to get perf pmu type".to_string(), e.to_string())
+        })
+    }
+
+    pub(crate) fn set_pmu_config(&mut self, config: u64) -> Result<(), BpfmanError> {
+        sled_insert(&self.data.db_tree, PERF_EVENT_PMU_CONFIG, &config.to_ne_bytes())
+    }
+
+    pub fn get_pmu_config(&self) -> Result<u64, BpfmanError> {
+        sled_get(&self.data.db_tree, PERF_EVENT_PMU_CONFIG).map(bytes_to_u64)
+    }
+
+    pub(crate) fn set_sample_policy(
+        &mut self,
+        policy: PerfSamplePolicy,
+    ) -> Result<(), BpfmanError> {
+        match policy {
+            PerfSamplePolicy::Frequency(f) => {
+                sled_insert(&self.data.db_tree, PERF_EVENT_SAMPLE_FREQ, &f.to_ne_bytes())
+            }
+            PerfSamplePolicy::Period(p) => {
+                sled_insert(&self.data.db_tree, PERF_EVENT_SAMPLE_PERIOD, &p.to_ne_bytes())
+            }
+        }
+    }
+
+    pub fn get_sample_policy(&self) -> Result<PerfSamplePolicy, BpfmanError> {
+        if let Some(f) = sled_get_option(&self.data.db_tree, PERF_EVENT_SAMPLE_FREQ)? {
+            Ok(PerfSamplePolicy::Frequency(bytes_to_u64(f)))
+        } else {
+            Ok(PerfSamplePolicy::Period(bytes_to_u64(sled_get(
+                &self.data.db_tree,
+                PERF_EVENT_SAMPLE_PERIOD,
+            )?)))
+        }
+    }
+
+    pub(crate) fn set_scope(&mut self, scope: PerfScope) -> Result<(), BpfmanError> {
+        match scope {
+            PerfScope::Cpu(cpu) => {
+                sled_insert(&self.data.db_tree, PERF_EVENT_CPU, &cpu.to_ne_bytes())
+            }
+            // Leaving the CPU key unset denotes "all online CPUs".
+            PerfScope::AllCpus => Ok(()),
+        }
+    }
+
+    pub fn get_scope(&self) -> Result<PerfScope, BpfmanError> {
+        Ok(match sled_get_option(&self.data.db_tree, PERF_EVENT_CPU)? {
+            Some(cpu) => PerfScope::Cpu(bytes_to_i32(cpu)),
+            None => PerfScope::AllCpus,
+        })
+    }
+
+    pub(crate) fn set_attached(&mut self, attached: bool) -> Result<(), BpfmanError> {
+        sled_insert(
+            &self.data.db_tree,
+            PERF_EVENT_ATTACHED,
+            &(attached as i8).to_ne_bytes(),
+        )
+    }
+
+    pub fn get_attached(&self) -> Result<bool, BpfmanError> {
+        Ok(sled_get_option(&self.data.db_tree, PERF_EVENT_ATTACHED)?
+            .map(bytes_to_bool)
+            .unwrap_or(false))
+    }
+
+    pub(crate) fn get_data(&self) -> &ProgramData {
+        &self.data
+    }
+
+    pub(crate) fn get_data_mut(&mut self) -> &mut ProgramData {
+        &mut self.data
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UsdtProgram {
+    pub(crate) data: ProgramData,
+}
+
+impl UsdtProgram {
+    pub fn new(
+        data: ProgramData,
+        target: String,
+        provider: String,
+        probe: String,
+        cookie: Option<u64>,
+        pid: Option<i32>,
+        container_pid: Option<i32>,
+    ) -> Result<Self, BpfmanError> {
+        let mut usdt_prog = Self { data };
+
+        usdt_prog.set_target(target)?;
+        usdt_prog.set_provider(provider)?;
+        usdt_prog.set_probe(probe)?;
+        if let Some(c) = cookie {
+            usdt_prog.set_cookie(c)?;
+        }
+        if let Some(p) = pid {
+            usdt_prog.set_pid(p)?;
+        }
+        if let Some(p) = container_pid {
+            usdt_prog.set_container_pid(p)?;
+        }
+        usdt_prog.get_data_mut().set_kind(ProgramType::Probe)?;
+        Ok(usdt_prog)
+    }
+
+    pub(crate) fn set_target(&mut self, target: String) -> Result<(), BpfmanError> {
+        sled_insert(&self.data.db_tree, USDT_TARGET, target.as_bytes())
+    }
+
     pub fn get_target(&self) -> Result<String, BpfmanError> {
-        sled_get(&self.data.db_tree, UPROBE_TARGET).map(|v| bytes_to_string(&v))
+        sled_get(&self.data.db_tree, USDT_TARGET).map(|v| bytes_to_string(&v))
+    }
+
+    pub(crate) fn set_provider(&mut self, provider: String) -> Result<(), BpfmanError> {
+        sled_insert(&self.data.db_tree, USDT_PROVIDER, provider.as_bytes())
+    }
+
+    pub fn get_provider(&self) -> Result<String, BpfmanError> {
+        sled_get(&self.data.db_tree, USDT_PROVIDER).map(|v| bytes_to_string(&v))
+    }
+
+    pub(crate) fn set_probe(&mut self, probe: String) -> Result<(), BpfmanError> {
+        sled_insert(&self.data.db_tree, USDT_PROBE, probe.as_bytes())
+    }
+
+    pub fn get_probe(&self) -> Result<String, BpfmanError> {
+        sled_get(&self.data.db_tree, USDT_PROBE).map(|v| bytes_to_string(&v))
+    }
+
+    pub(crate) fn set_cookie(&mut self, cookie: u64) -> Result<(), BpfmanError> {
+        sled_insert(&self.data.db_tree, USDT_COOKIE, &cookie.to_ne_bytes())
+    }
+
+    pub fn get_cookie(&self) -> Result<Option<u64>, BpfmanError> {
+        Ok(sled_get_option(&self.data.db_tree, USDT_COOKIE)?.map(bytes_to_u64))
+    }
+
+    pub(crate) fn set_pid(&mut self, pid: i32) -> Result<(), BpfmanError> {
+        sled_insert(&self.data.db_tree, USDT_PID, &pid.to_ne_bytes())
+    }
+
+    pub fn get_pid(&self) -> Result<Option<i32>, BpfmanError> {
+        Ok(sled_get_option(&self.data.db_tree, USDT_PID)?.map(bytes_to_i32))
+    }
+
+    pub(crate) fn set_container_pid(&mut self, container_pid: i32) -> Result<(), BpfmanError> {
+        sled_insert(
+            &self.data.db_tree,
+            USDT_CONTAINER_PID,
+            &container_pid.to_ne_bytes(),
+        )
+    }
+
+    pub fn get_container_pid(&self) -> Result<Option<i32>, BpfmanError> {
+        Ok(sled_get_option(&self.data.db_tree, USDT_CONTAINER_PID)?.map(bytes_to_i32))
+    }
+
+    /// Resolve the probe's file offset and semaphore address by parsing the
+    /// target ELF's `.note.stapsdt` notes, and persist them for later
+    /// attachment and semaphore refcount bumping.
+    pub(crate) fn resolve_offset(&mut self, elf_bytes: &[u8]) -> Result<(), BpfmanError> {
+        let location =
+            crate::usdt::resolve_usdt(elf_bytes, &self.get_provider()?, &self.get_probe()?)?;
+        self.set_offset(location.offset)?;
+        self.set_semaphore(location.semaphore)?;
+        Ok(())
+    }
+
+    pub(crate) fn set_offset(&mut self, offset: u64) -> Result<(), BpfmanError> {
+        sled_insert(&self.data.db_tree, USDT_OFFSET, &offset.to_ne_bytes())
+    }
+
+    pub fn get_offset(&self) -> Result<u64, BpfmanError> {
+        sled_get(&self.data.db_tree, USDT_OFFSET).map(bytes_to_u64)
+    }
+
+    pub(crate) fn set_semaphore(&mut self, semaphore: u64) -> Result<(), BpfmanError> {
+        sled_insert(&self.data.db_tree, USDT_SEMAPHORE, &semaphore.to_ne_bytes())
+    }
+
+    pub fn get_semaphore(&self) -> Result<u64, BpfmanError> {
+        sled_get(&self.data.db_tree, USDT_SEMAPHORE).map(bytes_to_u64)
     }
 
     pub(crate) fn get_data(&self) -> &ProgramData {
@@ -1471,19 +2031,382 @@ impl FexitProgram {
     }
 }
 
-impl Program {
-    pub fn kind(&self) -> ProgramType {
-        match self {
-            Program::Xdp(_) => ProgramType::Xdp,
-            Program::Tc(_) => ProgramType::Tc,
-            Program::Tracepoint(_) => ProgramType::Tracepoint,
-            Program::Kprobe(_) => ProgramType::Probe,
-            Program::Uprobe(_) => ProgramType::Probe,
-            Program::Fentry(_) => ProgramType::Tracing,
-            Program::Fexit(_) => ProgramType::Tracing,
-            Program::Unsupported(i) => i.get_kernel_program_type().unwrap().try_into().unwrap(),
-        }
-    }
+/// The two sk_skb sub-kinds the kernel collapses into a single program type:
+/// a stream parser and a stream verdict. bpfman records which one was loaded
+/// so it can be reconstructed from the database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkSkbSubKind {
+    StreamParser,
+    StreamVerdict,
+}
+
+impl From<SkSkbSubKind> for u32 {
+    fn from(value: SkSkbSubKind) -> Self {
+        match value {
+            SkSkbSubKind::StreamParser => 0,
+            SkSkbSubKind::StreamVerdict => 1,
+        }
+    }
+}
+
+impl TryFrom<u32> for SkSkbSubKind {
+    type Error = ParseError;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => SkSkbSubKind::StreamParser,
+            1 => SkSkbSubKind::StreamVerdict,
+            other => {
+                return Err(ParseError::InvalidProgramType {
+                    program: format!("sk_skb sub-kind {other}"),
+                })
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SkSkbProgram {
+    pub(crate) data: ProgramData,
+}
+
+impl SkSkbProgram {
+    pub fn new(data: ProgramData, sub_kind: SkSkbSubKind) -> Result<Self, BpfmanError> {
+        let mut prog = Self { data };
+        prog.set_sub_kind(sub_kind)?;
+        prog.get_data_mut().set_kind(ProgramType::SkSkb)?;
+        Ok(prog)
+    }
+
+    pub(crate) fn set_sub_kind(&mut self, sub_kind: SkSkbSubKind) -> Result<(), BpfmanError> {
+        sled_insert(
+            &self.data.db_tree,
+            SK_SKB_SUB_KIND,
+            &Into::<u32>::into(sub_kind).to_ne_bytes(),
+        )
+    }
+
+    pub fn get_sub_kind(&self) -> Result<SkSkbSubKind, BpfmanError> {
+        let value = bytes_to_u32(sled_get(&self.data.db_tree, SK_SKB_SUB_KIND)?);
+        SkSkbSubKind::try_from(value).map_err(|e| {
+            BpfmanError::DatabaseError("Failed to get sk_skb sub kind".to_string(), e.to_string())
+        })
+    }
+
+    /// The id of the sockmap/sockhash the program is (or will be) attached to.
+    pub(crate) fn set_map_id(&mut self, map_id: u32) -> Result<(), BpfmanError> {
+        sled_insert(&self.data.db_tree, SK_SKB_MAP_ID, &map_id.to_ne_bytes())
+    }
+
+    pub fn get_map_id(&self) -> Result<Option<u32>, BpfmanError> {
+        Ok(sled_get_option(&self.data.db_tree, SK_SKB_MAP_ID)?.map(bytes_to_u32))
+    }
+
+    pub(crate) fn get_data(&self) -> &ProgramData {
+        &self.data
+    }
+
+    pub(crate) fn get_data_mut(&mut self) -> &mut ProgramData {
+        &mut self.data
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SkMsgProgram {
+    pub(crate) data: ProgramData,
+}
+
+impl SkMsgProgram {
+    pub fn new(data: ProgramData) -> Result<Self, BpfmanError> {
+        let mut prog = Self { data };
+        prog.get_data_mut().set_kind(ProgramType::SkMsg)?;
+        Ok(prog)
+    }
+
+    /// The id of the sockmap/sockhash the program is (or will be) attached to.
+    pub(crate) fn set_map_id(&mut self, map_id: u32) -> Result<(), BpfmanError> {
+        sled_insert(&self.data.db_tree, SK_MSG_MAP_ID, &map_id.to_ne_bytes())
+    }
+
+    pub fn get_map_id(&self) -> Result<Option<u32>, BpfmanError> {
+        Ok(sled_get_option(&self.data.db_tree, SK_MSG_MAP_ID)?.map(bytes_to_u32))
+    }
+
+    pub(crate) fn get_data(&self) -> &ProgramData {
+        &self.data
+    }
+
+    pub(crate) fn get_data_mut(&mut self) -> &mut ProgramData {
+        &mut self.data
+    }
+}
+
+/// The cgroup v2 attach type of a cgroup-scoped program, matching the relevant
+/// subset of the kernel `bpf_attach_type` enum. The numeric values mirror the
+/// kernel so they can be round-tripped through the gRPC API unchanged.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+pub enum CgroupAttachType {
+    Ingress,
+    Egress,
+    InetSockCreate,
+    SockOps,
+    Device,
+    Bind4,
+    Bind6,
+    Connect4,
+    Connect6,
+    PostBind4,
+    PostBind6,
+    Sendmsg4,
+    Sendmsg6,
+    Sysctl,
+    Recvmsg4,
+    Recvmsg6,
+    Getsockopt,
+    Setsockopt,
+    InetSockRelease,
+}
+
+impl TryFrom<i32> for CgroupAttachType {
+    type Error = ParseError;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => CgroupAttachType::Ingress,
+            1 => CgroupAttachType::Egress,
+            2 => CgroupAttachType::InetSockCreate,
+            3 => CgroupAttachType::SockOps,
+            6 => CgroupAttachType::Device,
+            8 => CgroupAttachType::Bind4,
+            9 => CgroupAttachType::Bind6,
+            10 => CgroupAttachType::Connect4,
+            11 => CgroupAttachType::Connect6,
+            12 => CgroupAttachType::PostBind4,
+            13 => CgroupAttachType::PostBind6,
+            14 => CgroupAttachType::Sendmsg4,
+            15 => CgroupAttachType::Sendmsg6,
+            18 => CgroupAttachType::Sysctl,
+            19 => CgroupAttachType::Recvmsg4,
+            20 => CgroupAttachType::Recvmsg6,
+            21 => CgroupAttachType::Getsockopt,
+            22 => CgroupAttachType::Setsockopt,
+            34 => CgroupAttachType::InetSockRelease,
+            other => {
+                return Err(ParseError::InvalidCgroupAttachType {
+                    attach_type: other.to_string(),
+                })
+            }
+        })
+    }
+}
+
+impl From<CgroupAttachType> for i32 {
+    fn from(value: CgroupAttachType) -> Self {
+        match value {
+            CgroupAttachType::Ingress => 0,
+            CgroupAttachType::Egress => 1,
+            CgroupAttachType::InetSockCreate => 2,
+            CgroupAttachType::SockOps => 3,
+            CgroupAttachType::Device => 6,
+            CgroupAttachType::Bind4 => 8,
+            CgroupAttachType::Bind6 => 9,
+            CgroupAttachType::Connect4 => 10,
+            CgroupAttachType::Connect6 => 11,
+            CgroupAttachType::PostBind4 => 12,
+            CgroupAttachType::PostBind6 => 13,
+            CgroupAttachType::Sendmsg4 => 14,
+            CgroupAttachType::Sendmsg6 => 15,
+            CgroupAttachType::Sysctl => 18,
+            CgroupAttachType::Recvmsg4 => 19,
+            CgroupAttachType::Recvmsg6 => 20,
+            CgroupAttachType::Getsockopt => 21,
+            CgroupAttachType::Setsockopt => 22,
+            CgroupAttachType::InetSockRelease => 34,
+        }
+    }
+}
+
+impl std::fmt::Display for CgroupAttachType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let v = match self {
+            CgroupAttachType::Ingress => "ingress",
+            CgroupAttachType::Egress => "egress",
+            CgroupAttachType::InetSockCreate => "sock_create",
+            CgroupAttachType::SockOps => "sock_ops",
+            CgroupAttachType::Device => "device",
+            CgroupAttachType::Bind4 => "bind4",
+            CgroupAttachType::Bind6 => "bind6",
+            CgroupAttachType::Connect4 => "connect4",
+            CgroupAttachType::Connect6 => "connect6",
+            CgroupAttachType::PostBind4 => "post_bind4",
+            CgroupAttachType::PostBind6 => "post_bind6",
+            CgroupAttachType::Sendmsg4 => "sendmsg4",
+            CgroupAttachType::Sendmsg6 => "sendmsg6",
+            CgroupAttachType::Sysctl => "sysctl",
+            CgroupAttachType::Recvmsg4 => "recvmsg4",
+            CgroupAttachType::Recvmsg6 => "recvmsg6",
+            CgroupAttachType::Getsockopt => "getsockopt",
+            CgroupAttachType::Setsockopt => "setsockopt",
+            CgroupAttachType::InetSockRelease => "sock_release",
+        };
+        write!(f, "{v}")
+    }
+}
+
+/// A cgroup-scoped program.
+///
+/// `sock_ops`, `cgroup_skb`, `cgroup_sock`, `cgroup_sock_addr`, and
+/// `cgroup_sockopt` programs all attach to a cgroup v2 directory with a
+/// [`CgroupAttachType`], so they share one backing struct analogous to the way
+/// XDP and TC both carry an `if_index`. The owning [`Program`] variant records
+/// which `ProgramType` the cgroup program is.
+#[derive(Debug, Clone)]
+pub struct CgroupProgram {
+    pub(crate) data: ProgramData,
+}
+
+impl CgroupProgram {
+    pub fn new(
+        data: ProgramData,
+        kind: ProgramType,
+        cgroup_path: String,
+        attach_type: CgroupAttachType,
+    ) -> Result<Self, BpfmanError> {
+        let mut prog = Self { data };
+
+        prog.set_cgroup_path(cgroup_path)?;
+        prog.set_attach_type(attach_type)?;
+        prog.get_data_mut().set_kind(kind)?;
+
+        Ok(prog)
+    }
+
+    pub(crate) fn set_cgroup_path(&mut self, cgroup_path: String) -> Result<(), BpfmanError> {
+        sled_insert(&self.data.db_tree, CGROUP_PATH, cgroup_path.as_bytes())
+    }
+
+    pub fn get_cgroup_path(&self) -> Result<String, BpfmanError> {
+        sled_get(&self.data.db_tree, CGROUP_PATH).map(|v| bytes_to_string(&v))
+    }
+
+    pub(crate) fn set_attach_type(
+        &mut self,
+        attach_type: CgroupAttachType,
+    ) -> Result<(), BpfmanError> {
+        sled_insert(
+            &self.data.db_tree,
+            CGROUP_ATTACH_TYPE,
+            &i32::from(attach_type).to_ne_bytes(),
+        )
+    }
+
+    pub fn get_attach_type(&self) -> Result<CgroupAttachType, BpfmanError> {
+        let value = bytes_to_i32(sled_get(&self.data.db_tree, CGROUP_ATTACH_TYPE)?);
+        CgroupAttachType::try_from(value).map_err(|e| {
+            BpfmanError::DatabaseError("Failed to get cgroup attach type".to_string(), e.to_string())
+        })
+    }
+
+    pub(crate) fn get_data(&self) -> &ProgramData {
+        &self.data
+    }
+
+    pub(crate) fn get_data_mut(&mut self) -> &mut ProgramData {
+        &mut self.data
+    }
+}
+
+/// A concrete inner program type that a [`Program`] can be downcast to via
+/// [`Program::try_as`]. Borrowing aya's `program::<&mut Xdp>()` ergonomics,
+/// this lets callers reach a typed accessor without the crate hand-maintaining
+/// a matching per-field method on `Program`.
+pub trait TypedProgram: Sized {
+    /// The program type this downcast target corresponds to.
+    const PROGRAM_TYPE: ProgramType;
+
+    /// Borrow the inner program if `program` is of this type.
+    fn downcast(program: &Program) -> Option<&Self>;
+
+    /// Mutably borrow the inner program if `program` is of this type.
+    fn downcast_mut(program: &mut Program) -> Option<&mut Self>;
+}
+
+macro_rules! impl_typed_program {
+    ($inner:ty, $variant:ident, $program_type:expr) => {
+        impl TypedProgram for $inner {
+            const PROGRAM_TYPE: ProgramType = $program_type;
+
+            fn downcast(program: &Program) -> Option<&Self> {
+                match program {
+                    Program::$variant(p) => Some(p),
+                    _ => None,
+                }
+            }
+
+            fn downcast_mut(program: &mut Program) -> Option<&mut Self> {
+                match program {
+                    Program::$variant(p) => Some(p),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+impl_typed_program!(XdpProgram, Xdp, ProgramType::Xdp);
+impl_typed_program!(TcProgram, Tc, ProgramType::Tc);
+impl_typed_program!(TracepointProgram, Tracepoint, ProgramType::Tracepoint);
+impl_typed_program!(KprobeProgram, Kprobe, ProgramType::Probe);
+impl_typed_program!(UprobeProgram, Uprobe, ProgramType::Probe);
+impl_typed_program!(UsdtProgram, Usdt, ProgramType::Probe);
+impl_typed_program!(PerfEventProgram, PerfEvent, ProgramType::PerfEvent);
+impl_typed_program!(SkSkbProgram, SkSkb, ProgramType::SkSkb);
+impl_typed_program!(SkMsgProgram, SkMsg, ProgramType::SkMsg);
+impl_typed_program!(FentryProgram, Fentry, ProgramType::Tracing);
+impl_typed_program!(FexitProgram, Fexit, ProgramType::Tracing);
+
+impl Program {
+    /// Downcast to a concrete inner program type, e.g.
+    /// `prog.try_as::<TcProgram>()?.get_direction()`. Returns a typed
+    /// "wrong program kind" error carrying the expected and actual
+    /// [`ProgramType`] when the program is of a different kind.
+    pub fn try_as<T: TypedProgram>(&self) -> Result<&T, BpfmanError> {
+        T::downcast(self).ok_or_else(|| BpfmanError::WrongProgramType {
+            expected: T::PROGRAM_TYPE,
+            actual: self.kind(),
+        })
+    }
+
+    /// Mutable counterpart to [`Program::try_as`].
+    pub fn try_as_mut<T: TypedProgram>(&mut self) -> Result<&mut T, BpfmanError> {
+        let actual = self.kind();
+        T::downcast_mut(self).ok_or(BpfmanError::WrongProgramType {
+            expected: T::PROGRAM_TYPE,
+            actual,
+        })
+    }
+
+    pub fn kind(&self) -> ProgramType {
+        match self {
+            Program::Xdp(_) => ProgramType::Xdp,
+            Program::Tc(_) => ProgramType::Tc,
+            Program::Tracepoint(_) => ProgramType::Tracepoint,
+            Program::Kprobe(_) => ProgramType::Probe,
+            Program::Uprobe(_) => ProgramType::Probe,
+            Program::Usdt(_) => ProgramType::Probe,
+            Program::PerfEvent(_) => ProgramType::PerfEvent,
+            Program::SkSkb(_) => ProgramType::SkSkb,
+            Program::SkMsg(_) => ProgramType::SkMsg,
+            Program::CgroupSockOps(_) => ProgramType::SockOps,
+            Program::CgroupSkb(_) => ProgramType::CgroupSkb,
+            Program::CgroupSock(_) => ProgramType::CgroupSock,
+            Program::CgroupSockAddr(_) => ProgramType::CgroupSockAddr,
+            Program::CgroupSockopt(_) => ProgramType::CgroupSockopt,
+            Program::Fentry(_) => ProgramType::Tracing,
+            Program::Fexit(_) => ProgramType::Tracing,
+            Program::Unsupported(i) => i.get_kernel_program_type().unwrap().try_into().unwrap(),
+        }
+    }
 
     pub(crate) fn dispatcher_id(&self) -> Result<Option<DispatcherId>, BpfmanError> {
         Ok(match self {
@@ -1508,6 +2431,15 @@ impl Program {
             Program::Tc(p) => &mut p.data,
             Program::Kprobe(p) => &mut p.data,
             Program::Uprobe(p) => &mut p.data,
+            Program::Usdt(p) => &mut p.data,
+            Program::PerfEvent(p) => &mut p.data,
+            Program::SkSkb(p) => &mut p.data,
+            Program::SkMsg(p) => &mut p.data,
+            Program::CgroupSockOps(p) => &mut p.data,
+            Program::CgroupSkb(p) => &mut p.data,
+            Program::CgroupSock(p) => &mut p.data,
+            Program::CgroupSockAddr(p) => &mut p.data,
+            Program::CgroupSockopt(p) => &mut p.data,
             Program::Fentry(p) => &mut p.data,
             Program::Fexit(p) => &mut p.data,
             Program::Unsupported(p) => p,
@@ -1515,9 +2447,14 @@ impl Program {
     }
 
     pub(crate) fn attached(&self) -> bool {
+        // Every type that tracks an attached flag must be consulted here, not
+        // just the legacy XDP/TC pair: a PerfEvent carries its own flag, so
+        // omitting it made `AttachmentState::Attached` silently drop attached
+        // perf-event programs. Types with no attach notion are never attached.
         match self {
             Program::Xdp(p) => p.get_attached().unwrap(),
             Program::Tc(p) => p.get_attached().unwrap(),
+            Program::PerfEvent(p) => p.get_attached().unwrap(),
             _ => false,
         }
     }
@@ -1526,6 +2463,7 @@ impl Program {
         match self {
             Program::Xdp(p) => p.set_attached(true).unwrap(),
             Program::Tc(p) => p.set_attached(true).unwrap(),
+            Program::PerfEvent(p) => p.set_attached(true).unwrap(),
             _ => (),
         };
     }
@@ -1602,6 +2540,30 @@ impl Program {
         }
     }
 
+    pub(crate) fn cgroup_path(&self) -> Result<String, BpfmanError> {
+        match self {
+            Program::CgroupSockOps(p)
+            | Program::CgroupSkb(p)
+            | Program::CgroupSock(p)
+            | Program::CgroupSockAddr(p)
+            | Program::CgroupSockopt(p) => p.get_cgroup_path(),
+            _ => Err(BpfmanError::Error(
+                "cannot get cgroup path on non-cgroup programs".to_string(),
+            )),
+        }
+    }
+
+    pub(crate) fn attach_type(&self) -> Result<Option<CgroupAttachType>, BpfmanError> {
+        match self {
+            Program::CgroupSockOps(p)
+            | Program::CgroupSkb(p)
+            | Program::CgroupSock(p)
+            | Program::CgroupSockAddr(p)
+            | Program::CgroupSockopt(p) => Ok(Some(p.get_attach_type()?)),
+            _ => Ok(None),
+        }
+    }
+
     pub fn get_data(&self) -> &ProgramData {
         match self {
             Program::Xdp(p) => p.get_data(),
@@ -1609,6 +2571,15 @@ impl Program {
             Program::Tc(p) => p.get_data(),
             Program::Kprobe(p) => p.get_data(),
             Program::Uprobe(p) => p.get_data(),
+            Program::Usdt(p) => p.get_data(),
+            Program::PerfEvent(p) => p.get_data(),
+            Program::SkSkb(p) => p.get_data(),
+            Program::SkMsg(p) => p.get_data(),
+            Program::CgroupSockOps(p) => p.get_data(),
+            Program::CgroupSkb(p) => p.get_data(),
+            Program::CgroupSock(p) => p.get_data(),
+            Program::CgroupSockAddr(p) => p.get_data(),
+            Program::CgroupSockopt(p) => p.get_data(),
             Program::Fentry(p) => p.get_data(),
             Program::Fexit(p) => p.get_data(),
             Program::Unsupported(p) => p,
@@ -1628,9 +2599,26 @@ impl Program {
                 ProgramType::Xdp => Ok(Program::Xdp(XdpProgram { data })),
                 ProgramType::Tc => Ok(Program::Tc(TcProgram { data })),
                 ProgramType::Tracepoint => Ok(Program::Tracepoint(TracepointProgram { data })),
+                ProgramType::SkSkb => Ok(Program::SkSkb(SkSkbProgram { data })),
+                ProgramType::SkMsg => Ok(Program::SkMsg(SkMsgProgram { data })),
+                // Cgroup-scoped programs all share `CgroupProgram`; the stored
+                // cgroup path and attach type are read back through it, and the
+                // stored program kind selects the enum variant.
+                ProgramType::SockOps => Ok(Program::CgroupSockOps(CgroupProgram { data })),
+                ProgramType::CgroupSkb => Ok(Program::CgroupSkb(CgroupProgram { data })),
+                ProgramType::CgroupSock => Ok(Program::CgroupSock(CgroupProgram { data })),
+                ProgramType::CgroupSockAddr => {
+                    Ok(Program::CgroupSockAddr(CgroupProgram { data }))
+                }
+                ProgramType::CgroupSockopt => Ok(Program::CgroupSockopt(CgroupProgram { data })),
                 // kernel does not distinguish between kprobe and uprobe program types
                 ProgramType::Probe => {
-                    if data.db_tree.get(UPROBE_OFFSET).unwrap().is_some() {
+                    // USDT probes are resolved from a provider/probe pair; a
+                    // plain uprobe stores a raw offset; everything else is a
+                    // kprobe.
+                    if data.db_tree.get(USDT_PROVIDER).unwrap().is_some() {
+                        Ok(Program::Usdt(UsdtProgram { data }))
+                    } else if data.db_tree.get(UPROBE_OFFSET).unwrap().is_some() {
                         Ok(Program::Uprobe(UprobeProgram { data }))
                     } else {
                         Ok(Program::Kprobe(KprobeProgram { data }))
@@ -1651,6 +2639,63 @@ impl Program {
     }
 }
 
+/// Reconstruct an [`OsString`] from the raw bytes persisted in the database.
+///
+/// Paths and target filenames are stored as their raw OS byte representation
+/// rather than UTF-8, so this is the counterpart to `OsStr::as_bytes` used on
+/// the write side. On Unix any byte sequence is a valid `OsString`, so the
+/// getters reconstruct the path through this rather than funnelling through
+/// `bytes_to_string` and panicking on non-UTF-8 input.
+pub(crate) fn bytes_to_os_string(bytes: &[u8]) -> OsString {
+    OsString::from_vec(bytes.to_vec())
+}
+
+/// Rebuild the shared-map used-by sets by scanning every program tree.
+///
+/// Run on startup so a crash mid-unload (which could leave a borrower recorded
+/// against an owner it no longer uses, or drop a borrower from the set) doesn't
+/// strand a pinned map forever. Each loaded program that borrows a map (has a
+/// `map_owner_id`) is re-registered against its owner's used-by set.
+pub(crate) fn reconcile_map_users(root_db: &Db) -> Result<(), BpfmanError> {
+    // owner id -> set of borrowing program ids
+    let mut owners: HashMap<u32, Vec<u32>> = HashMap::new();
+
+    for name in root_db.tree_names() {
+        if !name.starts_with(PROGRAM_PREFIX.as_bytes()) {
+            continue;
+        }
+        let tree = root_db
+            .open_tree(&name)
+            .expect("Unable to open program tree during reconciliation");
+        let data = ProgramData::new_empty(tree);
+
+        let id = match data.get_id() {
+            Ok(id) => id,
+            Err(_) => continue,
+        };
+        if let Some(owner) = data.get_map_owner_id()? {
+            owners.entry(owner).or_default().push(id);
+        } else {
+            // An owner is its own first user.
+            owners.entry(id).or_default().push(id);
+        }
+    }
+
+    for (owner, mut users) in owners {
+        let tree = root_db.open_tree(PROGRAM_PREFIX.to_string() + &owner.to_string());
+        let Ok(tree) = tree else { continue };
+        if tree.is_empty() {
+            continue;
+        }
+        users.sort_unstable();
+        users.dedup();
+        let mut data = ProgramData::new_empty(tree);
+        data.set_maps_used_by(users)?;
+    }
+
+    Ok(())
+}
+
 /// MapType must match the the bpf_map_type enum defined in the linux kernel.
 /// <https://elixir.bootlin.com/linux/v6.9.5/source/include/uapi/linux/bpf.h#L964>
 #[derive(Debug)]
@@ -2028,6 +3073,7 @@ pub enum ProbeType {
     Kretprobe,
     Uprobe,
     Uretprobe,
+    Usdt,
 }
 
 impl TryFrom<i32> for ProbeType {
@@ -2039,6 +3085,7 @@ impl TryFrom<i32> for ProbeType {
             1 => ProbeType::Kretprobe,
             2 => ProbeType::Uprobe,
             3 => ProbeType::Uretprobe,
+            4 => ProbeType::Usdt,
             other => {
                 return Err(ParseError::InvalidProbeType {
                     probe: other.to_string(),
@@ -2066,12 +3113,13 @@ impl std::fmt::Display for ProbeType {
             ProbeType::Kretprobe => "kretprobe",
             ProbeType::Uprobe => "uprobe",
             ProbeType::Uretprobe => "uretprobe",
+            ProbeType::Usdt => "usdt",
         };
         write!(f, "{v}")
     }
 }
 
-#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
 pub enum XdpProceedOnEntry {
     Aborted,
     Drop,
@@ -2081,25 +3129,6 @@ pub enum XdpProceedOnEntry {
     DispatcherReturn = 31,
 }
 
-impl FromIterator<XdpProceedOnEntry> for XdpProceedOn {
-    fn from_iter<I: IntoIterator<Item = XdpProceedOnEntry>>(iter: I) -> Self {
-        let mut c = Vec::new();
-
-        let mut iter = iter.into_iter().peekable();
-
-        // make sure to default if proceed on is empty
-        if iter.peek().is_none() {
-            return XdpProceedOn::default();
-        };
-
-        for i in iter {
-            c.push(i);
-        }
-
-        XdpProceedOn(c)
-    }
-}
-
 impl TryFrom<String> for XdpProceedOnEntry {
     type Error = ParseError;
     fn try_from(value: String) -> Result<Self, Self::Error> {
@@ -2152,64 +3181,42 @@ impl std::fmt::Display for XdpProceedOnEntry {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct XdpProceedOn(Vec<XdpProceedOnEntry>);
-impl Default for XdpProceedOn {
-    fn default() -> Self {
-        XdpProceedOn(vec![
-            XdpProceedOnEntry::Pass,
-            XdpProceedOnEntry::DispatcherReturn,
-        ])
+impl ProceedOnAction for XdpProceedOnEntry {
+    fn parse(value: &str) -> Result<Self, ParseError> {
+        value.to_string().try_into()
     }
-}
 
-impl XdpProceedOn {
-    pub fn from_strings<T: AsRef<[String]>>(values: T) -> Result<XdpProceedOn, ParseError> {
-        let entries = values.as_ref();
-        let mut res = vec![];
-        for e in entries {
-            res.push(e.to_owned().try_into()?)
-        }
-        Ok(XdpProceedOn(res))
+    fn to_i32(self) -> i32 {
+        self as i32
     }
 
-    pub fn from_int32s<T: AsRef<[i32]>>(values: T) -> Result<XdpProceedOn, ParseError> {
-        let entries = values.as_ref();
-        if entries.is_empty() {
-            return Ok(XdpProceedOn::default());
-        }
-        let mut res = vec![];
-        for e in entries {
-            res.push((*e).try_into()?)
-        }
-        Ok(XdpProceedOn(res))
+    fn try_from_i32(value: i32) -> Result<Self, ParseError> {
+        value.try_into()
     }
 
-    pub fn mask(&self) -> u32 {
-        let mut proceed_on_mask: u32 = 0;
-        for action in self.0.clone().into_iter() {
-            proceed_on_mask |= 1 << action as u32;
-        }
-        proceed_on_mask
+    // XDP return values are already valid shift amounts, so no offset is
+    // needed.
+    fn bit_offset() -> i32 {
+        0
     }
 
-    pub fn as_action_vec(&self) -> Vec<i32> {
-        let mut res = vec![];
-        for entry in &self.0 {
-            res.push((*entry) as i32)
-        }
-        res
+    fn default_set() -> Vec<Self> {
+        vec![XdpProceedOnEntry::Pass, XdpProceedOnEntry::DispatcherReturn]
     }
-}
 
-impl std::fmt::Display for XdpProceedOn {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let res: Vec<String> = self.0.iter().map(|x| x.to_string()).collect();
-        write!(f, "{}", res.join(", "))
+    fn all() -> Vec<Self> {
+        vec![
+            XdpProceedOnEntry::Aborted,
+            XdpProceedOnEntry::Drop,
+            XdpProceedOnEntry::Pass,
+            XdpProceedOnEntry::Tx,
+            XdpProceedOnEntry::Redirect,
+            XdpProceedOnEntry::DispatcherReturn,
+        ]
     }
 }
 
-#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
 pub enum TcProceedOnEntry {
     Unspec = -1,
     Ok = 0,
@@ -2291,89 +3298,315 @@ impl std::fmt::Display for TcProceedOnEntry {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct TcProceedOn(pub(crate) Vec<TcProceedOnEntry>);
-impl Default for TcProceedOn {
-    fn default() -> Self {
-        TcProceedOn(vec![
+impl ProceedOnAction for TcProceedOnEntry {
+    fn parse(value: &str) -> Result<Self, ParseError> {
+        value.to_string().try_into()
+    }
+
+    fn to_i32(self) -> i32 {
+        self as i32
+    }
+
+    fn try_from_i32(value: i32) -> Result<Self, ParseError> {
+        value.try_into()
+    }
+
+    // Valid TC return values range from -1 to 8. Since -1 is not a valid shift
+    // value, 1 is added to the value to determine the bit to set in the
+    // bitmask; correspondingly, the TC dispatcher adds 1 to the return value
+    // from the BPF program before comparing it to the configured bit mask.
+    fn bit_offset() -> i32 {
+        1
+    }
+
+    fn default_set() -> Vec<Self> {
+        vec![TcProceedOnEntry::Pipe, TcProceedOnEntry::DispatcherReturn]
+    }
+
+    fn all() -> Vec<Self> {
+        vec![
+            TcProceedOnEntry::Unspec,
+            TcProceedOnEntry::Ok,
+            TcProceedOnEntry::Reclassify,
+            TcProceedOnEntry::Shot,
             TcProceedOnEntry::Pipe,
+            TcProceedOnEntry::Stolen,
+            TcProceedOnEntry::Queued,
+            TcProceedOnEntry::Repeat,
+            TcProceedOnEntry::Redirect,
+            TcProceedOnEntry::Trap,
             TcProceedOnEntry::DispatcherReturn,
-        ])
+        ]
+    }
+}
+
+/// A single proceed-on action for a dispatcher program type.
+///
+/// XDP and TC proceed-on sets share all of their logic bar two details: the
+/// per-action shift offset applied when building the bitmask (TC adds 1
+/// because its `Unspec` action is `-1`), and the default entry set. Both are
+/// expressed through this trait so [`ProceedOnSet`] can serve both.
+pub trait ProceedOnAction: Copy + Clone + PartialEq + std::fmt::Display {
+    /// Parse a single action token such as `"pass"`.
+    fn parse(value: &str) -> Result<Self, ParseError>;
+    /// The action's raw kernel return value.
+    fn to_i32(self) -> i32;
+    /// Reconstruct an action from its raw kernel return value.
+    fn try_from_i32(value: i32) -> Result<Self, ParseError>;
+    /// The offset added to each action before it is used as a shift amount:
+    /// `0` for XDP, `1` for TC.
+    fn bit_offset() -> i32;
+    /// The action set used when none is specified.
+    fn default_set() -> Vec<Self>;
+    /// Every valid action for this program type, used to expand the `all`
+    /// token and to compute negated (`~`) sets.
+    fn all() -> Vec<Self>;
+}
+
+/// A set of proceed-on actions for a dispatcher program type, generic over the
+/// concrete [`ProceedOnAction`]. `XdpProceedOn` and `TcProceedOn` are aliases
+/// of this type.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ProceedOnSet<A: ProceedOnAction>(pub(crate) Vec<A>);
+
+pub type XdpProceedOn = ProceedOnSet<XdpProceedOnEntry>;
+pub type TcProceedOn = ProceedOnSet<TcProceedOnEntry>;
+
+impl<A: ProceedOnAction> Default for ProceedOnSet<A> {
+    fn default() -> Self {
+        ProceedOnSet(A::default_set())
     }
 }
 
-impl FromIterator<TcProceedOnEntry> for TcProceedOn {
-    fn from_iter<I: IntoIterator<Item = TcProceedOnEntry>>(iter: I) -> Self {
-        let mut c = Vec::new();
+impl<A: ProceedOnAction> FromIterator<A> for ProceedOnSet<A> {
+    fn from_iter<I: IntoIterator<Item = A>>(iter: I) -> Self {
         let mut iter = iter.into_iter().peekable();
 
         // make sure to default if proceed on is empty
         if iter.peek().is_none() {
-            return TcProceedOn::default();
+            return ProceedOnSet::default();
         };
 
-        for i in iter {
-            c.push(i);
-        }
-
-        TcProceedOn(c)
+        ProceedOnSet(iter.collect())
     }
 }
 
-impl TcProceedOn {
-    pub fn from_strings<T: AsRef<[String]>>(values: T) -> Result<TcProceedOn, ParseError> {
-        let entries = values.as_ref();
+impl<A: ProceedOnAction> ProceedOnSet<A> {
+    pub fn from_strings<T: AsRef<[String]>>(values: T) -> Result<Self, ParseError> {
         let mut res = vec![];
-        for e in entries {
-            res.push(e.to_owned().try_into()?)
+        for e in values.as_ref() {
+            res.push(A::parse(e)?)
         }
-        Ok(TcProceedOn(res))
+        Ok(ProceedOnSet(res))
     }
 
-    pub fn from_int32s<T: AsRef<[i32]>>(values: T) -> Result<TcProceedOn, ParseError> {
+    pub fn from_int32s<T: AsRef<[i32]>>(values: T) -> Result<Self, ParseError> {
         let entries = values.as_ref();
         if entries.is_empty() {
-            return Ok(TcProceedOn::default());
+            return Ok(ProceedOnSet::default());
         }
         let mut res = vec![];
         for e in entries {
-            res.push((*e).try_into()?)
+            res.push(A::try_from_i32(*e)?)
         }
-        Ok(TcProceedOn(res))
+        Ok(ProceedOnSet(res))
     }
 
-    // Valid TC return values range from -1 to 8.  Since -1 is not a valid shift value,
-    // 1 is added to the value to determine the bit to set in the bitmask and,
-    // correspondingly, The TC dispatcher adds 1 to the return value from the BPF program
-    // before it compares it to the configured bit mask.
-    pub fn mask(&self) -> u32 {
+    /// Build the dispatcher bitmask, rejecting any action whose shifted value
+    /// (`to_i32() + bit_offset()`) falls outside `0..32`. The previous
+    /// per-type `mask()` shifted by that amount unconditionally, which is
+    /// undefined behaviour for a shift of 32 or more.
+    pub fn mask(&self) -> Result<u32, ParseError> {
         let mut proceed_on_mask: u32 = 0;
-        for action in self.0.clone().into_iter() {
-            proceed_on_mask |= 1 << ((action as i32) + 1);
+        for action in &self.0 {
+            let shift = action.to_i32() + A::bit_offset();
+            if !(0..32).contains(&shift) {
+                return Err(ParseError::InvalidProceedOn {
+                    proceedon: action.to_string(),
+                });
+            }
+            proceed_on_mask |= 1 << shift;
         }
-        proceed_on_mask
+        Ok(proceed_on_mask)
     }
 
     pub fn as_action_vec(&self) -> Vec<i32> {
-        let mut res = vec![];
-        for entry in &self.0 {
-            res.push((*entry) as i32)
-        }
-        res
+        self.0.iter().map(|entry| entry.to_i32()).collect()
     }
 
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
+
+    /// Verify that the set survives a round trip through its raw integer form,
+    /// i.e. `from_int32s(self.as_action_vec()) == self`. This catches
+    /// malformed gRPC input before it reaches the dispatcher.
+    pub fn round_trip(&self) -> Result<(), ParseError> {
+        if Self::from_int32s(self.as_action_vec())? == *self {
+            Ok(())
+        } else {
+            Err(ParseError::InvalidProceedOn {
+                proceedon: self.to_string(),
+            })
+        }
+    }
 }
 
-impl std::fmt::Display for TcProceedOn {
+impl<A: ProceedOnAction> std::fmt::Display for ProceedOnSet<A> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let res: Vec<String> = self.0.iter().map(|x| x.to_string()).collect();
         write!(f, "{}", res.join(", "))
     }
 }
 
+impl<A: ProceedOnAction> std::str::FromStr for ProceedOnSet<A> {
+    type Err = ParseError;
+
+    /// Parse a single comma- or space-separated expression such as
+    /// `"pass, drop"` or `"pipe dispatcher_return"`. The token `all` expands
+    /// to every valid action; a leading `~` negates the listed actions,
+    /// yielding every action except those named. Empty input yields
+    /// [`Default`]. `Display` output of a non-empty set with no repeated
+    /// actions parses back to the same set.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim();
+        if trimmed.is_empty() {
+            return Ok(ProceedOnSet::default());
+        }
+
+        let (negate, body) = match trimmed.strip_prefix('~') {
+            Some(rest) => (true, rest),
+            None => (false, trimmed),
+        };
+
+        let mut entries: Vec<A> = Vec::new();
+        for token in body
+            .split([',', ' '])
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+        {
+            if token == "all" {
+                for action in A::all() {
+                    if !entries.contains(&action) {
+                        entries.push(action);
+                    }
+                }
+            } else {
+                let action = A::parse(token)?;
+                if !entries.contains(&action) {
+                    entries.push(action);
+                }
+            }
+        }
+
+        if negate {
+            entries = A::all()
+                .into_iter()
+                .filter(|action| !entries.contains(action))
+                .collect();
+        }
+
+        Ok(ProceedOnSet(entries))
+    }
+}
+
+#[cfg(test)]
+mod proceed_on_mask_tests {
+    use super::*;
+
+    /// A stand-in action whose shifted value lands outside `0..32`, used to
+    /// drive the overflow guard that the real XDP/TC actions cannot reach.
+    #[derive(Copy, Clone, PartialEq)]
+    struct OverflowAction;
+
+    impl std::fmt::Display for OverflowAction {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "overflow")
+        }
+    }
+
+    impl ProceedOnAction for OverflowAction {
+        fn parse(_value: &str) -> Result<Self, ParseError> {
+            Ok(OverflowAction)
+        }
+        fn to_i32(self) -> i32 {
+            40
+        }
+        fn try_from_i32(_value: i32) -> Result<Self, ParseError> {
+            Ok(OverflowAction)
+        }
+        fn bit_offset() -> i32 {
+            0
+        }
+        fn default_set() -> Vec<Self> {
+            vec![OverflowAction]
+        }
+        fn all() -> Vec<Self> {
+            vec![OverflowAction]
+        }
+    }
+
+    #[test]
+    fn mask_sets_expected_bits() {
+        let set: XdpProceedOn = "pass, drop".parse().unwrap();
+        // drop == 1, pass == 2.
+        assert_eq!(set.mask().unwrap(), (1 << 1) | (1 << 2));
+    }
+
+    #[test]
+    fn mask_accepts_highest_valid_shift() {
+        let set = ProceedOnSet(vec![XdpProceedOnEntry::DispatcherReturn]);
+        assert_eq!(set.mask().unwrap(), 1 << 31);
+    }
+
+    #[test]
+    fn mask_rejects_out_of_range_shift() {
+        let set = ProceedOnSet(vec![OverflowAction]);
+        assert!(set.mask().is_err());
+    }
+
+    #[test]
+    fn round_trip_accepts_valid_set() {
+        let set: TcProceedOn = "pipe, dispatcher_return".parse().unwrap();
+        assert!(set.round_trip().is_ok());
+    }
+}
+
+#[cfg(test)]
+mod proceed_on_parse_tests {
+    use super::*;
+
+    #[test]
+    fn empty_input_is_default() {
+        let set: XdpProceedOn = "".parse().unwrap();
+        assert_eq!(set, XdpProceedOn::default());
+    }
+
+    #[test]
+    fn all_expands_and_round_trips_through_display() {
+        let all: XdpProceedOn = "all".parse().unwrap();
+        assert_eq!(all.0, XdpProceedOnEntry::all());
+        let reparsed: XdpProceedOn = all.to_string().parse().unwrap();
+        assert_eq!(reparsed, all);
+    }
+
+    #[test]
+    fn negation_excludes_listed_actions_and_round_trips() {
+        let negated: XdpProceedOn = "~pass".parse().unwrap();
+        assert!(!negated.0.contains(&XdpProceedOnEntry::Pass));
+        assert_eq!(negated.0.len(), XdpProceedOnEntry::all().len() - 1);
+        let reparsed: XdpProceedOn = negated.to_string().parse().unwrap();
+        assert_eq!(reparsed, negated);
+    }
+
+    #[test]
+    fn space_and_comma_separators_are_equivalent() {
+        let commas: TcProceedOn = "pipe, shot".parse().unwrap();
+        let spaces: TcProceedOn = "pipe shot".parse().unwrap();
+        assert_eq!(commas, spaces);
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum ImagePullPolicy {
     Always,
@@ -2381,6 +3614,19 @@ pub enum ImagePullPolicy {
     Never,
 }
 
+impl ImagePullPolicy {
+    /// Decide whether an image should be pulled given whether it is already
+    /// present in the local store, centralising the Always/IfNotPresent/Never
+    /// semantics in one place.
+    pub fn should_pull(&self, already_present: bool) -> bool {
+        match self {
+            ImagePullPolicy::Always => true,
+            ImagePullPolicy::IfNotPresent => !already_present,
+            ImagePullPolicy::Never => false,
+        }
+    }
+}
+
 impl std::fmt::Display for ImagePullPolicy {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let v = match self {
@@ -2437,13 +3683,22 @@ impl From<ImagePullPolicy> for i32 {
 impl std::fmt::Display for Location {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self {
-            // Cast imagePullPolicy into it's concrete type so we can easily print.
-            Location::Image(i) => write!(
-                f,
-                "image: {{ url: {}, pullpolicy: {} }}",
-                i.image_url,
-                TryInto::<ImagePullPolicy>::try_into(i.image_pull_policy.clone()).unwrap()
-            ),
+            Location::Image(i) => match &i.digest {
+                Some(digest) => write!(
+                    f,
+                    "image: {{ url: {}, pullpolicy: {}, digest: {} }}",
+                    i.image_url, i.image_pull_policy, digest
+                ),
+                None => write!(
+                    f,
+                    "image: {{ url: {}, pullpolicy: {} }}",
+                    i.image_url, i.image_pull_policy
+                ),
+            },
+            Location::ImageArchive { path, digest } => match digest {
+                Some(digest) => write!(f, "image archive: {{ path: {path}, digest: {digest} }}"),
+                None => write!(f, "image archive: {{ path: {path} }}"),
+            },
             Location::File(p) => write!(f, "file: {{ path: {p} }}"),
         }
     }
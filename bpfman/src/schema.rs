@@ -1,5 +1,26 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    attachments (attachment_id) {
+        attachment_id -> Integer,
+        prog_id -> Integer,
+        iface -> Nullable<Text>,
+        if_index -> Nullable<Integer>,
+        direction -> Nullable<Text>,
+        priority -> Nullable<Integer>,
+        current_position -> Nullable<Integer>,
+    }
+}
+
+diesel::table! {
+    cgroup_program_data (id) {
+        id -> Integer,
+        prog_id -> Integer,
+        cgroup_path -> Text,
+        attach_type -> Integer,
+    }
+}
+
 diesel::table! {
     fentry_program_data (id) {
         id -> Integer,
@@ -109,6 +130,46 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    perf_event_program_data (id) {
+        id -> Integer,
+        prog_id -> Integer,
+        pmu_type -> Integer,
+        pmu_config -> BigInt,
+        sample_frequency -> Nullable<BigInt>,
+        sample_period -> Nullable<BigInt>,
+        cpu -> Nullable<Integer>,
+        attached -> Bool,
+    }
+}
+
+diesel::table! {
+    program_authorization (id) {
+        id -> Integer,
+        prog_id -> Integer,
+        owner_uid -> Integer,
+        owner_gid -> Integer,
+        capabilities -> Integer,
+    }
+}
+
+diesel::table! {
+    sk_msg_program_data (id) {
+        id -> Integer,
+        prog_id -> Integer,
+        map_id -> Integer,
+    }
+}
+
+diesel::table! {
+    sk_skb_program_data (id) {
+        id -> Integer,
+        prog_id -> Integer,
+        map_id -> Integer,
+        sub_kind -> Integer,
+    }
+}
+
 diesel::table! {
     tc_program_data (id) {
         id -> Integer,
@@ -144,6 +205,21 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    usdt_program_data (id) {
+        id -> Integer,
+        prog_id -> Integer,
+        target -> Text,
+        provider -> Text,
+        probe -> Text,
+        cookie -> Nullable<BigInt>,
+        pid -> Nullable<Integer>,
+        container_pid -> Nullable<Integer>,
+        offset -> BigInt,
+        semaphore_addr -> BigInt,
+    }
+}
+
 diesel::table! {
     xdp_program_data (id) {
         id -> Integer,
@@ -157,6 +233,8 @@ diesel::table! {
     }
 }
 
+diesel::joinable!(attachments -> program_data (prog_id));
+diesel::joinable!(cgroup_program_data -> program_data (prog_id));
 diesel::joinable!(fentry_program_data -> program_data (prog_id));
 diesel::joinable!(fexit_program_data -> program_data (prog_id));
 diesel::joinable!(global_data -> program_data (prog_id));
@@ -166,12 +244,19 @@ diesel::joinable!(maps -> program_data (bpfman_prog_id));
 diesel::joinable!(maps_to_programs -> maps (map_id));
 diesel::joinable!(maps_to_programs -> program_data (prog_id));
 diesel::joinable!(metadata -> program_data (prog_id));
+diesel::joinable!(perf_event_program_data -> program_data (prog_id));
+diesel::joinable!(program_authorization -> program_data (prog_id));
+diesel::joinable!(sk_msg_program_data -> program_data (prog_id));
+diesel::joinable!(sk_skb_program_data -> program_data (prog_id));
 diesel::joinable!(tc_program_data -> program_data (prog_id));
 diesel::joinable!(tracepoint_program_data -> program_data (prog_id));
 diesel::joinable!(uprobe_program_data -> program_data (prog_id));
+diesel::joinable!(usdt_program_data -> program_data (prog_id));
 diesel::joinable!(xdp_program_data -> program_data (prog_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    attachments,
+    cgroup_program_data,
     fentry_program_data,
     fexit_program_data,
     global_data,
@@ -181,9 +266,14 @@ diesel::allow_tables_to_appear_in_same_query!(
     maps,
     maps_to_programs,
     metadata,
+    perf_event_program_data,
+    program_authorization,
     program_data,
+    sk_msg_program_data,
+    sk_skb_program_data,
     tc_program_data,
     tracepoint_program_data,
     uprobe_program_data,
+    usdt_program_data,
     xdp_program_data,
 );
@@ -0,0 +1,271 @@
+// SPDX-License-Identifier: Apache-2.0
+// Copyright Authors of bpfman
+
+//! Resolution of USDT (user statically-defined tracing) probes.
+//!
+//! A USDT probe is a statically-defined tracepoint embedded in a user-space
+//! binary. The target ELF carries `.note.stapsdt` entries, each naming a
+//! provider and probe plus the instruction address and an optional semaphore
+//! address that must be incremented for the application to emit the trace. To
+//! attach a uprobe we resolve `(provider, probe)` to a file offset via those
+//! notes.
+
+use object::{
+    read::elf::{FileHeader, ProgramHeader},
+    Endianness, Object, ObjectSection,
+};
+
+use crate::errors::BpfmanError;
+
+/// A single `.note.stapsdt` record, decoded from the target ELF.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StapsdtNote {
+    pub provider: String,
+    pub probe: String,
+    /// The probe instruction address, as recorded in the note.
+    pub location: u64,
+    /// The link-time base address used to relocate `location`.
+    pub base: u64,
+    /// The semaphore address, or 0 when the probe has no semaphore.
+    pub semaphore: u64,
+}
+
+/// The resolved attach information for a matched USDT probe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UsdtLocation {
+    /// The file offset the underlying uprobe attaches at.
+    pub offset: u64,
+    /// The semaphore address to bump, or 0 when absent.
+    pub semaphore: u64,
+}
+
+/// Parse the `.note.stapsdt` section of `elf_bytes` and resolve the file offset
+/// for the `(provider, probe)` pair.
+///
+/// The offset is computed as `vaddr = location + (stapsdt_base - note.base)`,
+/// then translated to a file offset via the `PT_LOAD` segment whose virtual
+/// address range contains it. If no note matches, a
+/// [`BpfmanError::UsdtProbeNotFound`] is returned listing the available
+/// provider/probe pairs, mirroring `ProgramNotFoundInBytecode`.
+pub fn resolve_usdt(
+    elf_bytes: &[u8],
+    provider: &str,
+    probe: &str,
+) -> Result<UsdtLocation, BpfmanError> {
+    let notes = parse_stapsdt_notes(elf_bytes)?;
+
+    let note = notes
+        .iter()
+        .find(|n| n.provider == provider && n.probe == probe)
+        .ok_or_else(|| BpfmanError::UsdtProbeNotFound {
+            provider: provider.to_string(),
+            probe: probe.to_string(),
+            available: notes
+                .iter()
+                .map(|n| format!("{}:{}", n.provider, n.probe))
+                .collect(),
+        })?;
+
+    let file = object::File::parse(elf_bytes)
+        .map_err(|e| BpfmanError::Error(format!("unable to parse target ELF: {e}")))?;
+
+    // The `.stapsdt.base` section, if present, accounts for prelink/relocation
+    // shifting the note addresses away from their link-time values.
+    let stapsdt_base = file
+        .section_by_name(".stapsdt.base")
+        .map(|s| s.address())
+        .unwrap_or(note.base);
+    let adjust = stapsdt_base.wrapping_sub(note.base);
+    let vaddr = note.location.wrapping_add(adjust);
+
+    let offset = vaddr_to_file_offset(elf_bytes, vaddr).ok_or_else(|| {
+        BpfmanError::Error(format!(
+            "unable to map USDT vaddr {vaddr:#x} to a file offset in {provider}:{probe}"
+        ))
+    })?;
+
+    Ok(UsdtLocation {
+        offset,
+        semaphore: note.semaphore,
+    })
+}
+
+/// Decode every `.note.stapsdt` record in `elf_bytes`.
+pub fn parse_stapsdt_notes(elf_bytes: &[u8]) -> Result<Vec<StapsdtNote>, BpfmanError> {
+    let file = object::File::parse(elf_bytes)
+        .map_err(|e| BpfmanError::Error(format!("unable to parse target ELF: {e}")))?;
+
+    let section = match file.section_by_name(".note.stapsdt") {
+        Some(section) => section,
+        None => return Ok(Vec::new()),
+    };
+    let data = section
+        .data()
+        .map_err(|e| BpfmanError::Error(format!("unable to read .note.stapsdt: {e}")))?;
+
+    let little_endian = file.is_little_endian();
+    let mut notes = Vec::new();
+    let mut cursor = 0usize;
+
+    // Each note is a standard ELF note: name size, desc size, type (all u32),
+    // a NUL-padded name ("stapsdt"), then the descriptor of three addresses
+    // followed by three NUL-terminated strings.
+    while cursor + 12 <= data.len() {
+        let name_sz = read_u32(&data[cursor..], little_endian) as usize;
+        let desc_sz = read_u32(&data[cursor + 4..], little_endian) as usize;
+        cursor += 12; // skip name size, desc size, note type
+
+        cursor += align4(name_sz);
+        let desc = match data.get(cursor..cursor + desc_sz) {
+            Some(desc) => desc,
+            None => break,
+        };
+        cursor += align4(desc_sz);
+
+        if let Some(note) = parse_descriptor(desc, little_endian) {
+            notes.push(note);
+        }
+    }
+
+    Ok(notes)
+}
+
+fn parse_descriptor(desc: &[u8], little_endian: bool) -> Option<StapsdtNote> {
+    if desc.len() < 24 {
+        return None;
+    }
+    let location = read_u64(&desc[0..], little_endian);
+    let base = read_u64(&desc[8..], little_endian);
+    let semaphore = read_u64(&desc[16..], little_endian);
+
+    let mut strings = desc[24..].split(|b| *b == 0);
+    let provider = String::from_utf8_lossy(strings.next()?).into_owned();
+    let probe = String::from_utf8_lossy(strings.next()?).into_owned();
+
+    Some(StapsdtNote {
+        provider,
+        probe,
+        location,
+        base,
+        semaphore,
+    })
+}
+
+fn vaddr_to_file_offset(elf_bytes: &[u8], vaddr: u64) -> Option<u64> {
+    let header = object::elf::FileHeader64::<Endianness>::parse(elf_bytes).ok()?;
+    let endian = header.endian().ok()?;
+    for ph in header.program_headers(endian, elf_bytes).ok()? {
+        if ph.p_type(endian) != object::elf::PT_LOAD {
+            continue;
+        }
+        let p_vaddr = ph.p_vaddr(endian);
+        let p_memsz = ph.p_memsz(endian);
+        if vaddr >= p_vaddr && vaddr < p_vaddr + p_memsz {
+            return Some(vaddr - p_vaddr + ph.p_offset(endian));
+        }
+    }
+    None
+}
+
+fn align4(n: usize) -> usize {
+    (n + 3) & !3
+}
+
+fn read_u32(bytes: &[u8], little_endian: bool) -> u32 {
+    let b: [u8; 4] = bytes[..4].try_into().unwrap();
+    if little_endian {
+        u32::from_le_bytes(b)
+    } else {
+        u32::from_be_bytes(b)
+    }
+}
+
+fn read_u64(bytes: &[u8], little_endian: bool) -> u64 {
+    let b: [u8; 8] = bytes[..8].try_into().unwrap();
+    if little_endian {
+        u64::from_le_bytes(b)
+    } else {
+        u64::from_be_bytes(b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a `.note.stapsdt` descriptor body: three little-endian addresses
+    /// followed by the NUL-terminated provider and probe names.
+    fn descriptor(location: u64, base: u64, semaphore: u64, provider: &str, probe: &str) -> Vec<u8> {
+        let mut desc = Vec::new();
+        desc.extend_from_slice(&location.to_le_bytes());
+        desc.extend_from_slice(&base.to_le_bytes());
+        desc.extend_from_slice(&semaphore.to_le_bytes());
+        desc.extend_from_slice(provider.as_bytes());
+        desc.push(0);
+        desc.extend_from_slice(probe.as_bytes());
+        desc.push(0);
+        desc
+    }
+
+    #[test]
+    fn descriptor_decodes_addresses_and_names() {
+        let desc = descriptor(0x4011_22, 0x40_0000, 0x40_4010, "bpfman", "probe1");
+        let note = parse_descriptor(&desc, true).expect("descriptor decodes");
+        assert_eq!(note.provider, "bpfman");
+        assert_eq!(note.probe, "probe1");
+        assert_eq!(note.location, 0x4011_22);
+        assert_eq!(note.base, 0x40_0000);
+        assert_eq!(note.semaphore, 0x40_4010);
+    }
+
+    #[test]
+    fn descriptor_too_short_is_rejected() {
+        assert!(parse_descriptor(&[0u8; 16], true).is_none());
+    }
+
+    /// A minimal ELF64 little-endian file header plus a single `PT_LOAD`
+    /// segment, enough for [`vaddr_to_file_offset`] to translate an address.
+    fn elf_with_one_load(p_offset: u64, p_vaddr: u64, p_memsz: u64) -> Vec<u8> {
+        let mut elf = Vec::new();
+        // e_ident: magic, 64-bit class, little-endian, current version.
+        elf.extend_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0]);
+        elf.extend_from_slice(&[0u8; 8]); // e_ident padding
+        elf.extend_from_slice(&2u16.to_le_bytes()); // e_type = ET_EXEC
+        elf.extend_from_slice(&0x3eu16.to_le_bytes()); // e_machine = x86-64
+        elf.extend_from_slice(&1u32.to_le_bytes()); // e_version
+        elf.extend_from_slice(&0u64.to_le_bytes()); // e_entry
+        elf.extend_from_slice(&64u64.to_le_bytes()); // e_phoff
+        elf.extend_from_slice(&0u64.to_le_bytes()); // e_shoff
+        elf.extend_from_slice(&0u32.to_le_bytes()); // e_flags
+        elf.extend_from_slice(&64u16.to_le_bytes()); // e_ehsize
+        elf.extend_from_slice(&56u16.to_le_bytes()); // e_phentsize
+        elf.extend_from_slice(&1u16.to_le_bytes()); // e_phnum
+        elf.extend_from_slice(&0u16.to_le_bytes()); // e_shentsize
+        elf.extend_from_slice(&0u16.to_le_bytes()); // e_shnum
+        elf.extend_from_slice(&0u16.to_le_bytes()); // e_shstrndx
+
+        // One PT_LOAD program header.
+        elf.extend_from_slice(&1u32.to_le_bytes()); // p_type = PT_LOAD
+        elf.extend_from_slice(&5u32.to_le_bytes()); // p_flags = R+X
+        elf.extend_from_slice(&p_offset.to_le_bytes());
+        elf.extend_from_slice(&p_vaddr.to_le_bytes());
+        elf.extend_from_slice(&p_vaddr.to_le_bytes()); // p_paddr
+        elf.extend_from_slice(&p_memsz.to_le_bytes()); // p_filesz
+        elf.extend_from_slice(&p_memsz.to_le_bytes()); // p_memsz
+        elf.extend_from_slice(&0x1000u64.to_le_bytes()); // p_align
+        elf
+    }
+
+    #[test]
+    fn vaddr_inside_load_maps_to_file_offset() {
+        let elf = elf_with_one_load(0x1000, 0x40_0000, 0x2000);
+        // vaddr - p_vaddr + p_offset = 0x1234 + 0x1000.
+        assert_eq!(vaddr_to_file_offset(&elf, 0x40_1234), Some(0x2234));
+    }
+
+    #[test]
+    fn vaddr_outside_any_load_is_unmapped() {
+        let elf = elf_with_one_load(0x1000, 0x40_0000, 0x2000);
+        assert_eq!(vaddr_to_file_offset(&elf, 0x50_0000), None);
+    }
+}